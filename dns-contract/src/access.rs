@@ -0,0 +1,158 @@
+use near_sdk::json_types::U64;
+use near_sdk::{AccountId, near};
+
+use crate::names::normalize_name;
+use crate::records::RECORD_TYPES;
+
+/// Permission for an editor to change records
+///
+/// `pattern` selects names:
+/// * an exact name, e.g. `www` or `@`
+/// * `*` for every name
+/// * `prefix.*` for `prefix` itself and every name whose leftmost labels are
+///   `prefix.`, e.g. `_acme-challenge.*` covers `_acme-challenge` and
+///   `_acme-challenge.www`; this matches by name prefix, so it spans names
+///   across the zone, not a subtree (`*.suffix` is the subtree form)
+/// * `*.suffix` for every name ending in `.suffix`, e.g. `*.staging`
+///
+/// `record_types` limits the grant to those types; empty allows all types.
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grant {
+    pub pattern: String,
+    #[serde(default)]
+    pub record_types: Vec<String>,
+}
+
+impl Grant {
+    /// Validate the pattern and record types, returning the grant in
+    /// canonical form
+    pub fn normalized(self) -> Result<Self, String> {
+        let pattern = if self.pattern == "*" {
+            self.pattern
+        } else if let Some(prefix) = self.pattern.strip_suffix(".*") {
+            format!("{}.*", normalize_name(prefix)?)
+        } else if let Some(suffix) = self.pattern.strip_prefix("*.") {
+            format!("*.{}", normalize_name(suffix)?)
+        } else {
+            normalize_name(&self.pattern)?
+        };
+
+        let mut record_types = Vec::with_capacity(self.record_types.len());
+        for record_type in self.record_types {
+            let record_type = record_type.to_uppercase();
+            if !RECORD_TYPES.contains(&record_type.as_str()) {
+                return Err(format!(
+                    "Unsupported record type '{}' in grant",
+                    record_type
+                ));
+            }
+            if !record_types.contains(&record_type) {
+                record_types.push(record_type);
+            }
+        }
+        Ok(Self {
+            pattern,
+            record_types,
+        })
+    }
+
+    /// Whether the grant covers `record_type` records of `name`
+    ///
+    /// `name` must already be normalized.
+    pub fn allows(&self, name: &str, record_type: &str) -> bool {
+        let type_allowed = self.record_types.is_empty()
+            || self
+                .record_types
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(record_type));
+        type_allowed && self.matches(name)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        if self.pattern == "*" {
+            return true;
+        }
+        if let Some(prefix) = self.pattern.strip_suffix(".*") {
+            return name == prefix
+                || name
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('.'));
+        }
+        if let Some(suffix) = self.pattern.strip_prefix("*.") {
+            return name
+                .strip_suffix(suffix)
+                .is_some_and(|rest| rest.ends_with('.') && rest.len() > 1);
+        }
+        name == self.pattern
+    }
+}
+
+/// How long a proposed ownership transfer can be accepted: 7 days
+pub const OWNERSHIP_TRANSFER_TTL_NS: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Ownership transfer waiting for the new owner to accept
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingTransfer {
+    pub new_owner: AccountId,
+    /// Block timestamp (nanoseconds) after which the proposal can't be accepted
+    pub expires_at: U64,
+}
+
+impl PendingTransfer {
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expires_at.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grant(pattern: &str, record_types: &[&str]) -> Grant {
+        Grant {
+            pattern: pattern.to_string(),
+            record_types: record_types.iter().map(|t| t.to_string()).collect(),
+        }
+        .normalized()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_grant_patterns() {
+        let acme = grant("_ACME-challenge.*", &["txt"]);
+        assert_eq!(acme.pattern, "_acme-challenge.*");
+        assert!(acme.allows("_acme-challenge", "TXT"));
+        assert!(acme.allows("_acme-challenge.www", "TXT"));
+        assert!(!acme.allows("_acme-challenge.www", "A"));
+        assert!(!acme.allows("_acme-challengex", "TXT"));
+
+        let staging = grant("*.staging", &[]);
+        assert!(staging.allows("api.staging", "A"));
+        assert!(staging.allows("a.b.staging", "CNAME"));
+        assert!(!staging.allows("staging", "A"));
+        assert!(!staging.allows("xstaging", "A"));
+
+        let www = grant("www", &["A", "AAAA"]);
+        assert!(www.allows("www", "AAAA"));
+        assert!(!www.allows("www.api", "A"));
+
+        assert!(grant("*", &[]).allows("@", "MX"));
+    }
+
+    #[test]
+    fn test_invalid_grants() {
+        for (pattern, types) in [("a:b", vec![]), ("api.*.x", vec![]), ("www", vec!["HINFO"])] {
+            let grant = Grant {
+                pattern: pattern.to_string(),
+                record_types: types.iter().map(|t: &&str| t.to_string()).collect(),
+            };
+            assert!(
+                grant.normalized().is_err(),
+                "{} should be rejected",
+                pattern
+            );
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use near_sdk::{env, near};
+
+use crate::DnsRecord;
+use crate::names::normalize_name;
+
+/// Most operations accepted in one `dns_apply` call
+pub const MAX_OPERATIONS: usize = 100;
+
+/// One change in a `dns_apply` changeset
+///
+/// In JSON: `{"op": "add", "name": "www", "record": {...}}`,
+/// `{"op": "replace", "name": "www", "records": [...]}`,
+/// `{"op": "delete", "name": "www", "record_type": "A"}`,
+/// `{"op": "remove", "name": "www", "record": {...}}` or
+/// `{"op": "replace_record", "name": "www", "old": {...}, "new": {...}}`.
+#[near(serializers = [json])]
+#[derive(Clone, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// Append a record to the name's records of its type, unless it already
+    /// has one with the same value
+    Add { name: String, record: DnsRecord },
+    /// Replace the name's records of one type
+    Replace {
+        name: String,
+        records: Vec<DnsRecord>,
+    },
+    /// Delete the name's records of one type, or of every type when
+    /// `record_type` is omitted
+    Delete {
+        name: String,
+        record_type: Option<String>,
+    },
+    /// Remove the record with the same type and value as `record`
+    Remove { name: String, record: DnsRecord },
+    /// Swap the record with the same type and value as `old` for `new`,
+    /// which must be of the same type
+    ReplaceRecord {
+        name: String,
+        old: DnsRecord,
+        new: DnsRecord,
+    },
+}
+
+impl Operation {
+    /// Validate names and records, returning the operation in canonical form
+    pub fn validated(self) -> Result<Self, String> {
+        match self {
+            Operation::Add { name, record } => Ok(Operation::Add {
+                name: normalize_name(&name)?,
+                record: validate_record(record)?,
+            }),
+            Operation::Replace { name, records } => {
+                let name = normalize_name(&name)?;
+                if records.is_empty() {
+                    return Err("Records cannot be empty".to_string());
+                }
+                let records = records
+                    .into_iter()
+                    .map(validate_record)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if records
+                    .iter()
+                    .any(|r| r.record_type != records[0].record_type)
+                {
+                    return Err("All records must be of the same type".to_string());
+                }
+                Ok(Operation::Replace { name, records })
+            }
+            // Deleting doesn't need a valid name, so records stored under
+            // names from before validation can still be removed
+            Operation::Delete { name, record_type } => Ok(Operation::Delete {
+                name: name.to_lowercase(),
+                record_type: record_type.map(|t| t.to_uppercase()),
+            }),
+            // Likewise for removing a record, which may not be valid either
+            Operation::Remove { name, mut record } => {
+                record.record_type = record.record_type.to_uppercase();
+                Ok(Operation::Remove {
+                    name: name.to_lowercase(),
+                    record,
+                })
+            }
+            Operation::ReplaceRecord { name, mut old, new } => {
+                old.record_type = old.record_type.to_uppercase();
+                let new = validate_record(new)?;
+                if new.record_type != old.record_type {
+                    return Err(format!(
+                        "Cannot replace a {} record with a {} record",
+                        old.record_type, new.record_type
+                    ));
+                }
+                Ok(Operation::ReplaceRecord {
+                    name: normalize_name(&name)?,
+                    old,
+                    new,
+                })
+            }
+        }
+    }
+}
+
+/// Validate a record, returning it with its type in upper case
+///
+/// Records that would already be expired are rejected.
+pub fn validate_record(mut record: DnsRecord) -> Result<DnsRecord, String> {
+    record.validate()?;
+    if record.is_expired(env::block_timestamp()) {
+        return Err(format!(
+            "Invalid {} record: valid_until is in the past",
+            record.record_type.to_uppercase()
+        ));
+    }
+    record.record_type = record.record_type.to_uppercase();
+    Ok(record)
+}
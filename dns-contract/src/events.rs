@@ -0,0 +1,109 @@
+use near_sdk::json_types::U64;
+use near_sdk::serde_json::{self, json};
+use near_sdk::{AccountId, env, near};
+
+use crate::DnsRecord;
+use crate::access::Grant;
+
+/// NEP-297 standard name for events emitted by this contract
+pub const EVENT_STANDARD: &str = "near-dns";
+/// Version of the event format
+pub const EVENT_VERSION: &str = "1.0.0";
+
+/// Events logged as `EVENT_JSON:{"standard":"near-dns","version":..,"event":..,"data":[..]}`
+#[near(serializers = [json])]
+#[derive(Clone, Debug)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum DnsEvent {
+    /// A record was appended to a name's records of one type
+    DnsRecordAdded {
+        name: String,
+        record_type: String,
+        record: DnsRecord,
+        serial: U64,
+    },
+    /// A name's records of one type were replaced (`old` is empty if there were none)
+    DnsRecordsUpdated {
+        name: String,
+        record_type: String,
+        old: Vec<DnsRecord>,
+        new: Vec<DnsRecord>,
+        serial: U64,
+    },
+    /// A name's records of one type were deleted
+    DnsRecordsDeleted {
+        name: String,
+        record_type: String,
+        old: Vec<DnsRecord>,
+        serial: U64,
+    },
+    AdminAdded {
+        account_id: AccountId,
+    },
+    AdminRemoved {
+        account_id: AccountId,
+    },
+    GrantAdded {
+        account_id: AccountId,
+        grant: Grant,
+    },
+    /// `pattern` is `None` when every grant of the account was revoked
+    GrantRevoked {
+        account_id: AccountId,
+        pattern: Option<String>,
+    },
+    OwnershipTransferProposed {
+        owner: AccountId,
+        new_owner: AccountId,
+        expires_at: U64,
+    },
+    OwnershipTransferCancelled {
+        owner: AccountId,
+        new_owner: AccountId,
+    },
+    OwnershipTransferred {
+        old_owner: AccountId,
+        new_owner: AccountId,
+    },
+}
+
+impl DnsEvent {
+    pub fn emit(&self) {
+        env::log_str(&self.to_log());
+    }
+
+    fn to_log(&self) -> String {
+        let event = serde_json::to_value(self)
+            .unwrap_or_else(|e| env::panic_str(&format!("Failed to serialize event: {}", e)));
+        format!(
+            "EVENT_JSON:{}",
+            json!({
+                "standard": EVENT_STANDARD,
+                "version": EVENT_VERSION,
+                "event": event["event"],
+                "data": [event["data"]],
+            })
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_format() {
+        let log = DnsEvent::GrantRevoked {
+            account_id: "ci.alice.testnet".parse().unwrap(),
+            pattern: Some("_acme-challenge.*".to_string()),
+        }
+        .to_log();
+
+        let json: serde_json::Value =
+            serde_json::from_str(log.strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        assert_eq!(json["standard"], EVENT_STANDARD);
+        assert_eq!(json["event"], "grant_revoked");
+        assert_eq!(json["data"][0]["account_id"], "ci.alice.testnet");
+        assert_eq!(json["data"][0]["pattern"], "_acme-challenge.*");
+    }
+}
@@ -0,0 +1,118 @@
+use near_sdk::json_types::U64;
+use near_sdk::{AccountId, near};
+use std::collections::BTreeMap;
+
+use crate::DnsRecord;
+
+/// Most change sets the owner can choose to keep
+pub const MAX_HISTORY_RETENTION: u32 = 1000;
+/// Change sets returned by `dns_changes_since` when no `limit` is given
+pub const DEFAULT_CHANGES_LIMIT: u32 = 20;
+/// Most change sets returned by one `dns_changes_since` call
+pub const MAX_CHANGES_LIMIT: u32 = 100;
+
+/// How one name's records of one type changed in a transaction
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordChange {
+    pub name: String,
+    pub record_type: String,
+    /// Records before the change; empty if there were none
+    pub old: Vec<DnsRecord>,
+    /// Records after the change; empty if they were deleted
+    pub new: Vec<DnsRecord>,
+}
+
+/// Everything one transaction changed, stored under the serial it produced
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeSet {
+    pub serial: U64,
+    pub block_height: U64,
+    /// Block timestamp in nanoseconds
+    pub timestamp: U64,
+    /// Account that made the change
+    pub author: AccountId,
+    pub changes: Vec<RecordChange>,
+}
+
+/// History settings and the serials it covers
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryInfo {
+    /// Number of change sets kept; 0 when history is off
+    pub retention: u32,
+    /// Oldest serial whose state can still be reconstructed
+    pub oldest_serial: U64,
+    /// Current zone serial
+    pub latest_serial: U64,
+}
+
+/// Undo `change_sets`, newest first, on the current records of `name`
+///
+/// `records` holds the name's current records by type; the result is the
+/// state before the oldest of the change sets, with types that had no
+/// records left out.
+pub fn rewind<'a>(
+    name: &str,
+    mut records: BTreeMap<String, Vec<DnsRecord>>,
+    change_sets: impl Iterator<Item = &'a ChangeSet>,
+) -> BTreeMap<String, Vec<DnsRecord>> {
+    for change_set in change_sets {
+        for change in change_set.changes.iter().filter(|c| c.name == name) {
+            records.insert(change.record_type.clone(), change.old.clone());
+        }
+    }
+    records.retain(|_, records| !records.is_empty());
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::record;
+
+    fn change_set(serial: u64, changes: Vec<RecordChange>) -> ChangeSet {
+        ChangeSet {
+            serial: U64(serial),
+            block_height: U64(100 + serial),
+            timestamp: U64(0),
+            author: "alice.testnet".parse().unwrap(),
+            changes,
+        }
+    }
+
+    fn change(name: &str, old: &[&str], new: &[&str]) -> RecordChange {
+        RecordChange {
+            name: name.to_string(),
+            record_type: "A".to_string(),
+            old: old.iter().map(|v| record("A", v)).collect(),
+            new: new.iter().map(|v| record("A", v)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_rewind() {
+        // serial 1 adds www, 2 changes it, 3 deletes it and adds api
+        let history = [
+            change_set(1, vec![change("www", &[], &["10.0.0.1"])]),
+            change_set(2, vec![change("www", &["10.0.0.1"], &["10.0.0.2"])]),
+            change_set(
+                3,
+                vec![
+                    change("www", &["10.0.0.2"], &[]),
+                    change("api", &[], &["10.0.0.3"]),
+                ],
+            ),
+        ];
+
+        let at = |serial: u64| {
+            let newer = history.iter().rev().filter(|cs| cs.serial.0 > serial);
+            rewind("www", BTreeMap::new(), newer)
+        };
+        assert!(at(3).is_empty());
+        assert_eq!(at(2)["A"][0].value, "10.0.0.2");
+        assert_eq!(at(1)["A"][0].value, "10.0.0.1");
+        assert!(at(0).is_empty());
+    }
+}
@@ -1,15 +1,102 @@
-use near_sdk::store::IterableMap;
-use near_sdk::{env, near, AccountId, PanicOnDefault};
+use near_sdk::json_types::U64;
+use near_sdk::store::{IterableMap, IterableSet, Lazy, LookupMap};
+use std::collections::{BTreeMap, BTreeSet};
+use near_sdk::{env, near, AccountId, Gas, GasWeight, NearToken, PanicOnDefault, Promise};
+
+pub mod access;
+pub mod changes;
+pub mod events;
+pub mod history;
+pub mod lint;
+pub mod listing;
+pub mod migration;
+pub mod names;
+pub mod records;
+pub mod storage;
+pub mod version;
+
+use access::{Grant, PendingTransfer, OWNERSHIP_TRANSFER_TTL_NS};
+use changes::{validate_record, Operation, MAX_OPERATIONS};
+use events::DnsEvent;
+use history::{
+    rewind, ChangeSet, HistoryInfo, RecordChange, DEFAULT_CHANGES_LIMIT, MAX_CHANGES_LIMIT,
+    MAX_HISTORY_RETENTION,
+};
+use lint::Violation;
+use listing::{page_names, page_range, Page, RecordSet};
+use names::{ancestors, normalize_name, wildcard_below};
+use storage::{serialized_len as len, StorageCost, StorageUsage};
+use version::{VersionInfo, CAPABILITIES, STATE_VERSION};
 
 /// DNS record as stored in the contract
+///
+/// `value` is validated against `record_type` on write; see
+/// [`records::RecordData`] for the accepted formats.
 #[near(serializers = [borsh, json])]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct DnsRecord {
     pub record_type: String,
     pub value: String,
     pub ttl: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priority: Option<u16>,
+    /// Block timestamp, in nanoseconds, from which the record is served
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<U64>,
+    /// Block timestamp, in nanoseconds, from which the record is no longer
+    /// served; expired records can be removed with `dns_remove_expired`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<U64>,
+}
+
+/// Storage key for the records of one name and type
+///
+/// Borsh encodes both fields length-prefixed, so no choice of name or type can
+/// make two keys collide.
+#[near(serializers = [borsh])]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RecordKey {
+    pub name: String,
+    pub record_type: String,
+}
+
+/// Summary of the zone served by the contract
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZoneInfo {
+    /// Account the contract is deployed to, e.g. `dns.alice.near`
+    pub contract_id: AccountId,
+    pub owner: AccountId,
+    /// Zone serial, incremented by every call that changes records
+    pub serial: U64,
+    /// Number of names that have records
+    pub names: u32,
+    /// Number of (name, type) record sets
+    pub record_sets: u32,
+}
+
+/// Most queries accepted in one `dns_query_many` or `dns_resolve_many` call
+pub const MAX_QUERIES: usize = 50;
+
+/// One (name, type) pair in a batch query
+#[near(serializers = [json])]
+#[derive(Clone, Debug)]
+pub struct RecordQuery {
+    pub name: String,
+    pub record_type: String,
+}
+
+/// Answer to a query, as an authoritative server would give it
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resolution {
+    /// Name the answer comes from: the queried name, or the wildcard that
+    /// matched it (e.g. `*.api`)
+    pub name: String,
+    /// Records of the queried type; empty if the name only has other types
+    pub records: Vec<DnsRecord>,
+    /// Whether the name has records of other types
+    pub other_types: bool,
 }
 
 /// DNS Contract for storing and querying DNS records
@@ -17,14 +104,39 @@ pub struct DnsRecord {
 /// This contract is designed to be deployed as `dns.<account>.<tld>` 
 /// For example: `dns.alice.near` or `dns.myapp.testnet`
 /// 
-/// Only the parent account (e.g., `alice.near` for `dns.alice.near`) can update records.
+/// The parent account (e.g., `alice.near` for `dns.alice.near`) owns the zone.
+/// The owner appoints admins, who can edit any record and manage editors;
+/// editors can only change the names and record types their grants cover.
 #[near(contract_state)]
 #[derive(PanicOnDefault)]
 pub struct DnsContract {
-    /// DNS records stored as name:type -> Vec<DnsRecord>
-    records: IterableMap<String, Vec<DnsRecord>>,
+    /// Layout version of this state; see [`version::STATE_VERSION`]
+    version: u32,
+    /// DNS records stored as (name, type) -> Vec<DnsRecord>
+    records: IterableMap<RecordKey, Vec<DnsRecord>>,
+    /// Record types present for each name, sorted
+    names: IterableMap<String, Vec<String>>,
+    /// Names that have records, sorted, for paging in a stable order
+    sorted_names: Lazy<Vec<String>>,
+    /// Number of names with records below each name that has any, so names
+    /// with only names below them exist
+    descendants: LookupMap<String, u32>,
+    /// Accounts that can edit all records and manage editors
+    admins: IterableSet<AccountId>,
+    /// Editors and the names/types they may change
+    editors: IterableMap<AccountId, Vec<Grant>>,
     /// Owner of this DNS contract (parent account)
     owner: AccountId,
+    /// Ownership transfer waiting to be accepted
+    pending_owner: Option<PendingTransfer>,
+    /// Zone serial, incremented by every call that changes records
+    serial: u64,
+    /// Change sets by the serial they produced, for point-in-time queries
+    history: LookupMap<u64, ChangeSet>,
+    /// Number of change sets kept in `history`; 0 turns history off
+    history_retention: u32,
+    /// Oldest serial in `history`, or the next serial when it is empty
+    history_start: u64,
 }
 
 #[near]
@@ -40,10 +152,60 @@ impl DnsContract {
         
         env::log_str(&format!("DNS contract initialized. Owner: {}", owner));
         
-        Self {
-            records: IterableMap::new(b"r"),
+        let mut contract = Self {
+            version: STATE_VERSION,
+            records: IterableMap::new(b"k"),
+            names: IterableMap::new(b"n"),
+            sorted_names: Lazy::new(b"o", Vec::new()),
+            descendants: LookupMap::new(b"d"),
+            admins: IterableSet::new(b"a"),
+            editors: IterableMap::new(b"e"),
             owner,
-        }
+            pending_owner: None,
+            serial: 0,
+            history: LookupMap::new(b"h"),
+            history_retention: 0,
+            history_start: 1,
+        };
+        // Written now so record changes only pay for the names they add
+        contract.sorted_names.flush();
+        contract
+    }
+
+    /// Convert state written by earlier code to the current layout
+    ///
+    /// `upgrade` runs this right after deploying new code. It also converts
+    /// state from before versioning, which keyed records by `"name:TYPE"`
+    /// strings, and leaves state that is already current as it is.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        migration::migrate_state()
+    }
+
+    /// Deploy new code to this contract and migrate the state to it (owner
+    /// only)
+    ///
+    /// The call's input is the raw wasm, not JSON. Deploying and `migrate`
+    /// are one receipt, so if the migration fails the old code stays.
+    pub fn upgrade(&self) -> Promise {
+        self.assert_owner();
+        let code = env::input().unwrap_or_else(|| env::panic_str("Pass the new code as input"));
+        env::log_str(&format!(
+            "Upgrading from {} (state version {})",
+            env!("CARGO_PKG_VERSION"),
+            self.version
+        ));
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call_weight(
+                "migrate".to_string(),
+                Vec::new(),
+                NearToken::from_yoctonear(0),
+                Gas::from_gas(0),
+                GasWeight(1),
+            )
+            .as_return()
     }
 
     /// Check if the caller is the owner
@@ -51,14 +213,437 @@ impl DnsContract {
         assert_eq!(
             env::predecessor_account_id(),
             self.owner,
-            "Only the owner ({}) can modify DNS records",
+            "Only the owner ({}) can call this method",
             self.owner
         );
     }
 
+    /// Check if the caller is the owner or an admin
+    fn assert_admin(&self) {
+        let caller = env::predecessor_account_id();
+        assert!(
+            caller == self.owner || self.admins.contains(&caller),
+            "Only the owner or an admin can manage editors"
+        );
+    }
+
+    /// Check if the caller may change `record_type` records of `name`
+    fn assert_can_edit(&self, name: &str, record_type: &str) {
+        let caller = env::predecessor_account_id();
+        if caller == self.owner || self.admins.contains(&caller) {
+            return;
+        }
+        let granted = self
+            .editors
+            .get(&caller)
+            .is_some_and(|grants| grants.iter().any(|g| g.allows(name, record_type)));
+        assert!(
+            granted,
+            "Only the owner, an admin or an editor granted {} on '{}' can modify these records",
+            record_type.to_uppercase(),
+            name
+        );
+    }
+
+    fn assert_batch_size(queries: &[RecordQuery]) {
+        assert!(
+            queries.len() <= MAX_QUERIES,
+            "At most {} queries can be made in one call",
+            MAX_QUERIES
+        );
+    }
+
+    /// Validate a record, panicking with the reason if it is invalid
+    ///
+    /// Returns the record with its type normalized to upper case.
+    fn validated(record: DnsRecord) -> DnsRecord {
+        validate_record(record).unwrap_or_else(|reason| env::panic_str(&reason))
+    }
+
+    /// Validate a name, panicking with the reason if it is invalid
+    ///
+    /// Returns the name in canonical (lower-case) form.
+    fn validated_name(name: &str) -> String {
+        normalize_name(name).unwrap_or_else(|reason| env::panic_str(&reason))
+    }
+
     /// Build the storage key for a name and record type
-    fn make_key(name: &str, record_type: &str) -> String {
-        format!("{}:{}", name.to_lowercase(), record_type.to_uppercase())
+    fn make_key(name: &str, record_type: &str) -> RecordKey {
+        RecordKey {
+            name: name.to_lowercase(),
+            record_type: record_type.to_uppercase(),
+        }
+    }
+
+    /// Store the records for a key and add its type to the name's index,
+    /// returning the records it replaced
+    fn put_records(&mut self, key: RecordKey, records: Vec<DnsRecord>) -> Option<Vec<DnsRecord>> {
+        if !self.names.contains_key(&key.name) {
+            self.index_name(&key.name, true);
+        }
+        let types = self.names.entry(key.name.clone()).or_default();
+        if let Err(pos) = types.binary_search(&key.record_type) {
+            types.insert(pos, key.record_type.clone());
+        }
+        self.records.insert(key, records)
+    }
+
+    /// Apply validated operations for the caller, who must be allowed to
+    /// edit every record set they touch, and leave the names they write
+    /// within the coexistence rules
+    fn apply_changes(&mut self, operations: Vec<Operation>) {
+        self.commit_changes(operations, true);
+    }
+
+    /// Apply validated operations as one zone change: settle storage once,
+    /// and advance the serial and emit events if anything changed
+    ///
+    /// With `checked`, each operation is checked against the caller's
+    /// permissions just before it is applied, and once all are applied every
+    /// name they wrote records to must pass [`lint::check_name`]. Rules are
+    /// checked at the end so one change can, say, swap a name's address
+    /// records for a CNAME.
+    fn commit_changes(&mut self, operations: Vec<Operation>, checked: bool) {
+        let initial_usage = env::storage_usage();
+        let serial = U64(self.serial + 1);
+
+        // Names given records, whose coexistence is checked at the end;
+        // removing records can't break the rules
+        let written: BTreeSet<String> = operations
+            .iter()
+            .filter_map(|operation| match operation {
+                Operation::Add { name, .. }
+                | Operation::Replace { name, .. }
+                | Operation::ReplaceRecord { name, .. } => Some(name.clone()),
+                Operation::Delete { .. } | Operation::Remove { .. } => None,
+            })
+            .collect();
+
+        // Records of every key the changes touch, as they were before them
+        let keep_history = self.history_retention > 0;
+        let mut before = BTreeMap::new();
+
+        let mut events = Vec::new();
+        for operation in operations {
+            if checked || keep_history {
+                for key in self.keys_touched(&operation) {
+                    if checked {
+                        self.assert_can_edit(&key.name, &key.record_type);
+                    }
+                    if keep_history {
+                        let old = self.records.get(&key).cloned().unwrap_or_default();
+                        before.entry(key).or_insert(old);
+                    }
+                }
+            }
+            events.extend(self.apply_operation(operation, serial));
+        }
+        if checked {
+            for name in &written {
+                if let Some(problem) = self.lint_name(name).into_iter().next() {
+                    env::panic_str(&problem);
+                }
+            }
+        }
+        if !events.is_empty() {
+            self.serial = serial.0;
+            // Written before settling, so the caller pays for the change set
+            if keep_history {
+                self.record_history(before);
+            } else {
+                self.history_start = self.serial + 1;
+            }
+        }
+        self.settle_storage(initial_usage);
+
+        for event in events {
+            event.emit();
+        }
+    }
+
+    /// Keys of the record sets an operation can change
+    fn keys_touched(&self, operation: &Operation) -> Vec<RecordKey> {
+        match operation {
+            Operation::Add { name, record } | Operation::Remove { name, record } => {
+                vec![Self::make_key(name, &record.record_type)]
+            }
+            Operation::ReplaceRecord { name, old, .. } => {
+                vec![Self::make_key(name, &old.record_type)]
+            }
+            Operation::Replace { name, records } => {
+                vec![Self::make_key(name, &records[0].record_type)]
+            }
+            Operation::Delete { name, record_type } => match record_type {
+                Some(rt) => vec![Self::make_key(name, rt)],
+                None => self
+                    .types_of(name)
+                    .iter()
+                    .map(|rt| Self::make_key(name, rt))
+                    .collect(),
+            },
+        }
+    }
+
+    /// Store the change set for the current serial and drop the oldest ones
+    /// beyond the retention
+    fn record_history(&mut self, before: BTreeMap<RecordKey, Vec<DnsRecord>>) {
+        let changes = before
+            .into_iter()
+            .filter_map(|(key, old)| {
+                let new = self.records.get(&key).cloned().unwrap_or_default();
+                (old != new).then_some(RecordChange {
+                    name: key.name,
+                    record_type: key.record_type,
+                    old,
+                    new,
+                })
+            })
+            .collect();
+        self.history.insert(
+            self.serial,
+            ChangeSet {
+                serial: U64(self.serial),
+                block_height: U64(env::block_height()),
+                timestamp: U64(env::block_timestamp()),
+                author: env::predecessor_account_id(),
+                changes,
+            },
+        );
+        self.prune_history();
+    }
+
+    /// Remove change sets beyond the newest `history_retention`
+    fn prune_history(&mut self) {
+        let keep_from = (self.serial + 1).saturating_sub(self.history_retention as u64);
+        while self.history_start < keep_from {
+            self.history.remove(&self.history_start);
+            self.history_start += 1;
+        }
+    }
+
+    /// Check that the state at `serial` can be reconstructed
+    fn assert_history_covers(&self, serial: u64) {
+        assert!(
+            serial <= self.serial,
+            "Serial {} is newer than the zone serial {}",
+            serial,
+            self.serial
+        );
+        assert!(
+            serial + 1 >= self.history_start,
+            "History is only kept back to serial {}",
+            self.history_start - 1
+        );
+    }
+
+    /// Apply one validated operation, returning the events it produced
+    ///
+    /// Doesn't check permissions; see `commit_changes`.
+    fn apply_operation(&mut self, operation: Operation, serial: U64) -> Vec<DnsEvent> {
+        match operation {
+            Operation::Add { name, record } => {
+                let key = Self::make_key(&name, &record.record_type);
+                let mut existing = self.records.get(&key).cloned().unwrap_or_default();
+                assert!(
+                    !existing.iter().any(|r| r.same_data(&record)),
+                    "'{}' already has the {} record '{}'",
+                    name,
+                    record.record_type,
+                    record.value
+                );
+                existing.push(record.clone());
+                self.put_records(key, existing);
+                vec![DnsEvent::DnsRecordAdded {
+                    name,
+                    record_type: record.record_type.clone(),
+                    record,
+                    serial,
+                }]
+            }
+            Operation::Replace { name, records } => {
+                let record_type = records[0].record_type.clone();
+                let key = Self::make_key(&name, &record_type);
+                let old = self.put_records(key, records.clone()).unwrap_or_default();
+                vec![DnsEvent::DnsRecordsUpdated {
+                    name,
+                    record_type,
+                    old,
+                    new: records,
+                    serial,
+                }]
+            }
+            Operation::Delete { name, record_type } => {
+                // Delete one record type, or every type this name has
+                let types = match record_type {
+                    Some(rt) => vec![rt],
+                    None => self.types_of(&name),
+                };
+                let mut events = Vec::new();
+                for rt in types {
+                    let key = Self::make_key(&name, &rt);
+                    if let Some(old) = self.remove_records(&key) {
+                        events.push(DnsEvent::DnsRecordsDeleted {
+                            name: name.clone(),
+                            record_type: key.record_type,
+                            old,
+                            serial,
+                        });
+                    }
+                }
+                events
+            }
+            Operation::Remove { name, record } => {
+                let key = Self::make_key(&name, &record.record_type);
+                let old = self.records_matching(&name, &key, &record);
+                let new = old
+                    .iter()
+                    .filter(|r| !r.same_data(&record))
+                    .cloned()
+                    .collect();
+                self.set_records(name, key, new, serial)
+            }
+            Operation::ReplaceRecord { name, old, new } => {
+                let key = Self::make_key(&name, &old.record_type);
+                let mut records = self.records_matching(&name, &key, &old);
+                assert!(
+                    new.same_data(&old) || !records.iter().any(|r| r.same_data(&new)),
+                    "'{}' already has the {} record '{}'",
+                    name,
+                    new.record_type,
+                    new.value
+                );
+                for record in records.iter_mut().filter(|r| r.same_data(&old)) {
+                    *record = new.clone();
+                }
+                self.set_records(name, key, records, serial)
+            }
+        }
+    }
+
+    /// Records stored under `key`, which must include one with the same type
+    /// and value as `record`
+    fn records_matching(&self, name: &str, key: &RecordKey, record: &DnsRecord) -> Vec<DnsRecord> {
+        let records = self.records.get(key).cloned().unwrap_or_default();
+        assert!(
+            records.iter().any(|r| r.same_data(record)),
+            "'{}' has no {} record '{}'",
+            name,
+            record.record_type,
+            record.value
+        );
+        records
+    }
+
+    /// Store the new records for a key, or remove it if there are none,
+    /// returning the resulting event
+    fn set_records(
+        &mut self,
+        name: String,
+        key: RecordKey,
+        new: Vec<DnsRecord>,
+        serial: U64,
+    ) -> Vec<DnsEvent> {
+        let record_type = key.record_type.clone();
+        if new.is_empty() {
+            let old = self.remove_records(&key).unwrap_or_default();
+            vec![DnsEvent::DnsRecordsDeleted {
+                name,
+                record_type,
+                old,
+                serial,
+            }]
+        } else {
+            let old = self.put_records(key, new.clone()).unwrap_or_default();
+            vec![DnsEvent::DnsRecordsUpdated {
+                name,
+                record_type,
+                old,
+                new,
+                serial,
+            }]
+        }
+    }
+
+    /// Remove the records for a key and drop its type from the name's index
+    fn remove_records(&mut self, key: &RecordKey) -> Option<Vec<DnsRecord>> {
+        let removed = self.records.remove(key);
+        if let Some(types) = self.names.get_mut(&key.name) {
+            types.retain(|t| *t != key.record_type);
+            if types.is_empty() {
+                self.names.remove(&key.name);
+                self.index_name(&key.name, false);
+            }
+        }
+        removed
+    }
+
+    /// Add a name that gained records to the sorted names and count it for
+    /// each of its ancestors below the apex, or undo that once it has none
+    fn index_name(&mut self, name: &str, added: bool) {
+        let sorted = self.sorted_names.get_mut();
+        match (sorted.binary_search_by(|n| n.as_str().cmp(name)), added) {
+            (Err(pos), true) => sorted.insert(pos, name.to_string()),
+            (Ok(pos), false) => {
+                sorted.remove(pos);
+            }
+            _ => {}
+        }
+        for ancestor in ancestors(name).filter(|ancestor| *ancestor != "@") {
+            let count = self.descendants.get(ancestor).copied().unwrap_or(0);
+            let count = if added { count + 1 } else { count.saturating_sub(1) };
+            if count == 0 {
+                self.descendants.remove(ancestor);
+            } else {
+                self.descendants.insert(ancestor.to_string(), count);
+            }
+        }
+    }
+
+    /// Whether a name exists: the apex, a name with records, or a name with
+    /// names with records below it (an empty non-terminal)
+    fn name_exists(&self, name: &str) -> bool {
+        name == "@" || self.names.contains_key(name) || self.descendants.contains_key(name)
+    }
+
+    /// Write cached collection changes and settle their storage cost
+    fn settle_storage(&mut self, initial_usage: u64) {
+        self.records.flush();
+        self.names.flush();
+        self.sorted_names.flush();
+        self.descendants.flush();
+        self.admins.flush();
+        self.editors.flush();
+        self.history.flush();
+        storage::settle(initial_usage, &self.owner);
+    }
+
+    /// Every record stored for a name, of all types
+    fn all_records(&self, name: &str) -> Vec<DnsRecord> {
+        self.types_of(name)
+            .iter()
+            .filter_map(|rt| self.records.get(&Self::make_key(name, rt)))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Coexistence problems with a name's records
+    fn lint_name(&self, name: &str) -> Vec<String> {
+        lint::check_name(name, &self.all_records(name), env::block_timestamp())
+    }
+
+    /// Records stored under a key that are active at the current block
+    fn active_records(&self, key: &RecordKey) -> Vec<DnsRecord> {
+        let now = env::block_timestamp();
+        self.records
+            .get(key)
+            .map(|records| records.iter().filter(|r| r.is_active(now)).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Record types stored for a name
+    fn types_of(&self, name: &str) -> Vec<String> {
+        self.names.get(&name.to_lowercase()).cloned().unwrap_or_default()
     }
 
     // ========== VIEW METHODS ==========
@@ -69,54 +654,417 @@ impl DnsContract {
     /// * `name` - The DNS name to query (e.g., "@" for root, "www", "mail", "*" for wildcard)
     /// * `record_type` - The record type (e.g., "A", "AAAA", "CNAME", "MX", "TXT")
     /// 
+    /// Records outside their `valid_from`/`valid_until` window at the current
+    /// block are left out.
+    ///
     /// # Returns
     /// * `Option<Vec<DnsRecord>>` - The matching records, or None if not found
     pub fn dns_query(&self, name: String, record_type: String) -> Option<Vec<DnsRecord>> {
-        let key = Self::make_key(&name, &record_type);
-        self.records.get(&key).cloned()
+        let records = self.active_records(&Self::make_key(&name, &record_type));
+        (!records.is_empty()).then_some(records)
     }
 
-    /// Get all records for a given name (all types)
+    /// Get all records for a given name (all types) that are active at the
+    /// current block
     pub fn dns_query_all(&self, name: String) -> Vec<DnsRecord> {
         let mut all_records = Vec::new();
-        let record_types = ["A", "AAAA", "CNAME", "MX", "NS", "TXT", "SRV", "SOA", "PTR", "CAA"];
-        
-        for rt in record_types {
-            let key = Self::make_key(&name, rt);
-            if let Some(records) = self.records.get(&key) {
-                all_records.extend(records.clone());
-            }
+        for rt in self.types_of(&name) {
+            let key = Self::make_key(&name, &rt);
+            all_records.extend(self.active_records(&key));
         }
         
         all_records
     }
 
-    /// List all DNS names that have records
-    pub fn dns_list_names(&self) -> Vec<String> {
-        self.records
-            .keys()
-            .map(|k| {
-                // Extract name from "name:TYPE" key format
-                k.split(':').next().unwrap_or(k).to_string()
-            })
-            .collect::<std::collections::HashSet<_>>()
+    /// Resolve a query in one call, matching wildcards as in RFC 4592
+    ///
+    /// A name with records of any type is answered from its own records, and
+    /// a name that only has names with records below it exists without
+    /// records. Any other name is answered by the wildcard directly below its
+    /// closest existing ancestor (`*` under `@`), if there is one; broader
+    /// wildcards further up are not used.
+    ///
+    /// Only records active at the current block are returned, but a name
+    /// whose records are all inactive still exists until they are removed.
+    ///
+    /// # Returns
+    /// * `None` if the name doesn't exist (NXDOMAIN)
+    /// * `Some` with empty `records` if it exists without records of this type
+    pub fn dns_resolve(&self, name: String, record_type: String) -> Option<Resolution> {
+        let name = name.to_lowercase();
+        let record_type = record_type.to_uppercase();
+
+        let owner = if self.name_exists(&name) {
+            name
+        } else {
+            let encloser = ancestors(&name).find(|ancestor| self.name_exists(ancestor))?;
+            let wildcard = wildcard_below(encloser);
+            if !self.names.contains_key(&wildcard) {
+                return None;
+            }
+            wildcard
+        };
+
+        let records = self.active_records(&Self::make_key(&owner, &record_type));
+        let other_types = self.types_of(&owner).iter().any(|t| *t != record_type);
+        Some(Resolution {
+            name: owner,
+            records,
+            other_types,
+        })
+    }
+
+    /// Run several `dns_query` lookups in one call
+    ///
+    /// Results are in the same order as `queries`. At most 50 queries.
+    pub fn dns_query_many(&self, queries: Vec<RecordQuery>) -> Vec<Option<Vec<DnsRecord>>> {
+        Self::assert_batch_size(&queries);
+        queries
+            .into_iter()
+            .map(|q| self.dns_query(q.name, q.record_type))
+            .collect()
+    }
+
+    /// Run several `dns_resolve` lookups in one call
+    ///
+    /// Results are in the same order as `queries`. At most 50 queries.
+    pub fn dns_resolve_many(&self, queries: Vec<RecordQuery>) -> Vec<Option<Resolution>> {
+        Self::assert_batch_size(&queries);
+        queries
             .into_iter()
+            .map(|q| self.dns_resolve(q.name, q.record_type))
             .collect()
     }
 
-    /// List all records in the contract
+    /// List all DNS names that have records, sorted
+    ///
+    /// Reads every name, so large zones should use `dns_list_names_page`.
+    pub fn dns_list_names(&self) -> Vec<String> {
+        self.sorted_names.get().clone()
+    }
+
+    /// List all records in the contract, keyed as `"name:TYPE"`
+    ///
+    /// Reads every record, so large zones should use `dns_list_records_page`.
     pub fn dns_list_all(&self) -> Vec<(String, Vec<DnsRecord>)> {
         self.records
             .iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
+            .map(|(k, v)| (format!("{}:{}", k.name, k.record_type), v.clone()))
             .collect()
     }
 
+    /// List names in sorted order a page at a time, optionally only those
+    /// starting with `prefix`
+    ///
+    /// # Arguments
+    /// * `start_after` - Where to resume, from the previous page's `next`
+    /// * `limit` - Names to return (default 100, at most 500)
+    /// * `prefix` - Only return names starting with this, e.g. `_acme-challenge`
+    pub fn dns_list_names_page(
+        &self,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        prefix: Option<String>,
+    ) -> Page<String> {
+        let prefix = prefix.map(|p| p.to_lowercase()).unwrap_or_default();
+        let (names, next) =
+            page_names(self.sorted_names.get(), start_after.as_deref(), limit, &prefix);
+        Page { items: names.to_vec(), next }
+    }
+
+    /// List the record sets of a page of names, optionally filtered by name
+    /// prefix and record type
+    ///
+    /// Paging works as in `dns_list_names_page`, with `limit` counting names;
+    /// with a type filter the page can hold fewer record sets.
+    pub fn dns_list_records_page(
+        &self,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        prefix: Option<String>,
+        record_type: Option<String>,
+    ) -> Page<RecordSet> {
+        let prefix = prefix.map(|p| p.to_lowercase()).unwrap_or_default();
+        let record_type = record_type.map(|t| t.to_uppercase());
+        let (names, next) =
+            page_names(self.sorted_names.get(), start_after.as_deref(), limit, &prefix);
+        let mut items = Vec::new();
+        for name in names {
+            for found in self.names.get(name).into_iter().flatten() {
+                if record_type.as_ref().is_some_and(|wanted| wanted != found) {
+                    continue;
+                }
+                let key = Self::make_key(name, found);
+                if let Some(records) = self.records.get(&key) {
+                    items.push(RecordSet {
+                        name: key.name,
+                        record_type: key.record_type,
+                        records: records.clone(),
+                    });
+                }
+            }
+        }
+        Page { items, next }
+    }
+
+    /// Number of names that have records
+    pub fn dns_count_names(&self) -> u32 {
+        self.names.len()
+    }
+
+    /// Number of (name, type) record sets
+    pub fn dns_count_record_sets(&self) -> u32 {
+        self.records.len()
+    }
+
+    /// Check a page of names against the record coexistence rules
+    ///
+    /// New changes can't break the rules (a CNAME alone at its name and never
+    /// at `@`, SOA only at `@`), but records written before they were
+    /// enforced can. This lists them so the owner can fix them; it is open
+    /// to anyone, as view calls can't tell who is asking. Paging works as in
+    /// `dns_list_names_page`.
+    pub fn dns_lint(&self, start_after: Option<String>, limit: Option<u32>) -> Page<Violation> {
+        let (names, next) = page_names(self.sorted_names.get(), start_after.as_deref(), limit, "");
+        let items = names
+            .iter()
+            .flat_map(|name| {
+                self.lint_name(name).into_iter().map(|problem| Violation {
+                    name: name.clone(),
+                    problem,
+                })
+            })
+            .collect();
+        Page { items, next }
+    }
+
+    /// Version of the deployed code and of its state
+    pub fn get_version(&self) -> VersionInfo {
+        VersionInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            state_version: self.version,
+        }
+    }
+
+    /// Features the deployed code supports, so clients can tell what a zone
+    /// can do without probing its methods
+    ///
+    /// See [`version::CAPABILITIES`] for what each name covers.
+    pub fn dns_capabilities(&self) -> Vec<String> {
+        CAPABILITIES.iter().map(|c| c.to_string()).collect()
+    }
+
     /// Get the owner of this DNS contract
     pub fn get_owner(&self) -> AccountId {
         self.owner.clone()
     }
 
+    /// Current zone serial
+    ///
+    /// Starts at 0 and is incremented once by every call that changes
+    /// records, so secondaries and caches can tell whether the zone changed.
+    pub fn dns_zone_serial(&self) -> U64 {
+        U64(self.serial)
+    }
+
+    /// Serial, owner and size of the zone
+    pub fn dns_zone_info(&self) -> ZoneInfo {
+        ZoneInfo {
+            contract_id: env::current_account_id(),
+            owner: self.owner.clone(),
+            serial: U64(self.serial),
+            names: self.names.len(),
+            record_sets: self.records.len(),
+        }
+    }
+
+    /// History retention and the range of serials it covers
+    pub fn dns_history_info(&self) -> HistoryInfo {
+        HistoryInfo {
+            retention: self.history_retention,
+            oldest_serial: U64(self.history_start - 1),
+            latest_serial: U64(self.serial),
+        }
+    }
+
+    /// Change sets after `serial`, oldest first
+    ///
+    /// # Arguments
+    /// * `serial` - Return changes made after this serial
+    /// * `limit` - Most change sets to return (default 20, at most 100); ask
+    ///   again from the last returned serial for more
+    ///
+    /// # Panics
+    /// * If history older than `serial` is no longer kept
+    pub fn dns_changes_since(&self, serial: U64, limit: Option<u32>) -> Vec<ChangeSet> {
+        self.assert_history_covers(serial.0);
+        let limit = limit
+            .unwrap_or(DEFAULT_CHANGES_LIMIT)
+            .clamp(1, MAX_CHANGES_LIMIT);
+        (serial.0 + 1..=self.serial)
+            .take(limit as usize)
+            .filter_map(|s| self.history.get(&s).cloned())
+            .collect()
+    }
+
+    /// A name's records of every type as they were at `serial`
+    ///
+    /// # Panics
+    /// * If history older than `serial` is no longer kept
+    pub fn dns_records_at(&self, name: String, serial: U64) -> Vec<RecordSet> {
+        self.assert_history_covers(serial.0);
+        let name = name.to_lowercase();
+        let current = self
+            .types_of(&name)
+            .into_iter()
+            .filter_map(|rt| {
+                let records = self.records.get(&Self::make_key(&name, &rt))?;
+                Some((rt, records.clone()))
+            })
+            .collect();
+        let newer = (serial.0 + 1..=self.serial)
+            .rev()
+            .filter_map(|s| self.history.get(&s));
+        rewind(&name, current, newer)
+            .into_iter()
+            .map(|(record_type, records)| RecordSet {
+                name: name.clone(),
+                record_type,
+                records,
+            })
+            .collect()
+    }
+
+    /// Zone serial in effect at the end of block `block_height`
+    ///
+    /// Returns `None` if that is older than the kept history.
+    pub fn dns_serial_at_block(&self, block_height: U64) -> Option<U64> {
+        // First serial changed after the block; the one before it was in effect
+        let (mut low, mut high) = (self.history_start, self.serial + 1);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let at_or_before = self
+                .history
+                .get(&mid)
+                .is_some_and(|cs| cs.block_height.0 <= block_height.0);
+            if at_or_before {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if low == self.history_start && self.history_start > 1 {
+            return None;
+        }
+        Some(U64(low - 1))
+    }
+
+    /// Ownership transfer waiting to be accepted, if any (expired proposals
+    /// are not returned)
+    pub fn get_pending_owner(&self) -> Option<PendingTransfer> {
+        self.pending_owner
+            .clone()
+            .filter(|pending| !pending.is_expired(env::block_timestamp()))
+    }
+
+    /// List the admins
+    pub fn dns_list_admins(&self) -> Vec<AccountId> {
+        self.admins.iter().cloned().collect()
+    }
+
+    /// List editor grants, for one account or all editors
+    pub fn dns_list_grants(&self, account_id: Option<AccountId>) -> Vec<(AccountId, Vec<Grant>)> {
+        match account_id {
+            Some(account_id) => self
+                .editors
+                .get(&account_id)
+                .map(|grants| vec![(account_id, grants.clone())])
+                .unwrap_or_default(),
+            None => self
+                .editors
+                .iter()
+                .map(|(account_id, grants)| (account_id.clone(), grants.clone()))
+                .collect(),
+        }
+    }
+
+    /// Storage used by the contract and the balance locked for it
+    pub fn dns_storage_usage(&self) -> StorageUsage {
+        storage::current_usage()
+    }
+
+    /// Estimate the storage change and deposit for setting records
+    ///
+    /// Describes `dns_update(name, records)`, or `dns_add` of each record when
+    /// `append` is true. The estimate is exact for the record data. When
+    /// history is kept it includes the change set, counting its author at the
+    /// longest account ID and ignoring old change sets pruned to make room;
+    /// the deposit actually charged is measured when the change is made.
+    pub fn dns_storage_cost(
+        &self,
+        name: String,
+        records: Vec<DnsRecord>,
+        append: Option<bool>,
+    ) -> StorageCost {
+        let name = Self::validated_name(&name);
+        assert!(!records.is_empty(), "Records cannot be empty");
+        let mut records: Vec<DnsRecord> = records.into_iter().map(Self::validated).collect();
+        let key = Self::make_key(&name, &records[0].record_type);
+        let existing = self.records.get(&key);
+        if append.unwrap_or(false) {
+            if let Some(existing) = existing {
+                records.splice(0..0, existing.iter().cloned());
+            }
+        }
+
+        let key_len = len(&key);
+        let before_records = existing.map_or(0, |r| storage::map_entry_bytes(key_len, len(r)));
+        let after_records = storage::map_entry_bytes(key_len, len(&records));
+
+        let types = self.types_of(&name);
+        let new_name = types.is_empty();
+        let before_index = if new_name {
+            0
+        } else {
+            storage::map_entry_bytes(len(&name), len(&types))
+        };
+        let mut new_types = types;
+        if let Err(pos) = new_types.binary_search(&key.record_type) {
+            new_types.insert(pos, key.record_type.clone());
+        }
+        let after_index = storage::map_entry_bytes(len(&name), len(&new_types));
+
+        // A new name joins the sorted names and is counted by each ancestor,
+        // which gets an entry if it had no names below it
+        let new_descendants: u64 = if new_name {
+            ancestors(&name)
+                .filter(|ancestor| *ancestor != "@" && !self.descendants.contains_key(*ancestor))
+                .map(|ancestor| storage::lookup_entry_bytes(len(&ancestor), len(&0u32)))
+                .sum::<u64>()
+                + len(&name) as u64
+        } else {
+            0
+        };
+
+        let change_set = if self.history_retention > 0 {
+            let change = RecordChange {
+                name: key.name.clone(),
+                record_type: key.record_type.clone(),
+                old: existing.cloned().unwrap_or_default(),
+                new: records,
+            };
+            // Serial, block height and timestamp, then the author
+            let header = 3 * len(&0u64) + 4 + AccountId::MAX_LEN;
+            storage::lookup_entry_bytes(len(&0u64), header + len(&vec![change]))
+        } else {
+            0
+        };
+
+        let bytes = (after_records + after_index + new_descendants + change_set) as i64
+            - (before_records + before_index) as i64;
+        StorageCost::from_bytes(bytes)
+    }
+
     // ========== CHANGE METHODS ==========
 
     /// Update/set DNS records for a given name and record type
@@ -126,53 +1074,44 @@ impl DnsContract {
     /// * `name` - The DNS name (e.g., "@", "www", "mail", "*")
     /// * `records` - The records to set
     /// 
+    /// Attach a deposit for the storage added (see `dns_storage_cost`); any
+    /// excess is refunded.
+    /// 
     /// # Panics
-    /// * If caller is not the owner
+    /// * If caller is not allowed to edit this name and type
+    /// * If the name or any record is invalid
+    /// * If the attached deposit doesn't cover the added storage
+    #[payable]
     pub fn dns_update(&mut self, name: String, records: Vec<DnsRecord>) {
-        self.assert_owner();
-        
-        // Validate records
-        assert!(!records.is_empty(), "Records cannot be empty");
-        
-        // All records must be of the same type
-        let record_type = records[0].record_type.to_uppercase();
-        for r in &records {
-            assert_eq!(
-                r.record_type.to_uppercase(),
-                record_type,
-                "All records must be of the same type"
-            );
-        }
-        
-        let key = Self::make_key(&name, &record_type);
-        self.records.insert(key, records.clone());
-        
-        env::log_str(&format!(
-            "Updated {} {} record(s) for name '{}'",
-            records.len(),
-            record_type,
-            name
-        ));
+        let operation = Operation::Replace { name, records }
+            .validated()
+            .unwrap_or_else(|reason| env::panic_str(&reason));
+        self.apply_changes(vec![operation]);
     }
 
     /// Add a single DNS record (appends to existing records of the same type)
+    ///
+    /// A record with the same type and value as one the name already has is
+    /// rejected rather than added twice.
     /// 
     /// # Arguments
     /// * `name` - The DNS name
     /// * `record` - The record to add
+    ///
+    /// Attach a deposit for the storage added (see `dns_storage_cost`); any
+    /// excess is refunded.
+    ///
+    /// # Panics
+    /// * If caller is not allowed to edit this name and type
+    /// * If the name or record is invalid
+    /// * If the name already has a record with the same type and value
+    /// * If the attached deposit doesn't cover the added storage
+    #[payable]
     pub fn dns_add(&mut self, name: String, record: DnsRecord) {
-        self.assert_owner();
-        
-        let key = Self::make_key(&name, &record.record_type);
-        let mut existing = self.records.get(&key).cloned().unwrap_or_default();
-        existing.push(record.clone());
-        self.records.insert(key, existing);
-        
-        env::log_str(&format!(
-            "Added {} record for name '{}'",
-            record.record_type.to_uppercase(),
-            name
-        ));
+        let operation = Operation::Add { name, record }
+            .validated()
+            .unwrap_or_else(|reason| env::panic_str(&reason));
+        self.apply_changes(vec![operation]);
     }
 
     /// Delete DNS records
@@ -180,48 +1119,357 @@ impl DnsContract {
     /// # Arguments
     /// * `name` - The DNS name
     /// * `record_type` - Optional record type. If None, deletes all records for the name
+    ///
+    /// The balance locked for the released storage is refunded to the owner.
+    /// Deleting all records requires permission for every type the name has.
+    #[payable]
     pub fn dns_delete(&mut self, name: String, record_type: Option<String>) {
-        self.assert_owner();
-        
-        if let Some(rt) = record_type {
-            // Delete specific record type
-            let key = Self::make_key(&name, &rt);
-            self.records.remove(&key);
-            env::log_str(&format!("Deleted {} records for name '{}'", rt.to_uppercase(), name));
-        } else {
-            // Delete all record types for this name
-            let record_types = ["A", "AAAA", "CNAME", "MX", "NS", "TXT", "SRV", "SOA", "PTR", "CAA"];
-            for rt in record_types {
-                let key = Self::make_key(&name, rt);
-                self.records.remove(&key);
-            }
-            env::log_str(&format!("Deleted all records for name '{}'", name));
-        }
+        let operation = Operation::Delete { name, record_type }
+            .validated()
+            .unwrap_or_else(|reason| env::panic_str(&reason));
+        self.apply_changes(vec![operation]);
     }
 
-    /// Transfer ownership to a new account
-    /// 
-    /// # Arguments
-    /// * `new_owner` - The new owner account ID
-    pub fn transfer_ownership(&mut self, new_owner: AccountId) {
-        self.assert_owner();
-        let old_owner = self.owner.clone();
-        self.owner = new_owner.clone();
-        env::log_str(&format!("Ownership transferred from {} to {}", old_owner, new_owner));
+    /// Remove one record, matched by type and value, leaving the name's
+    /// other records of that type in place
+    ///
+    /// The record's TTL, priority and validity don't need to match. The
+    /// balance locked for the released storage is refunded to the owner.
+    ///
+    /// # Panics
+    /// * If caller is not allowed to edit this name and type
+    /// * If the name has no record with this type and value
+    #[payable]
+    pub fn dns_remove_record(&mut self, name: String, record: DnsRecord) {
+        let operation = Operation::Remove { name, record }
+            .validated()
+            .unwrap_or_else(|reason| env::panic_str(&reason));
+        self.apply_changes(vec![operation]);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::testing_env;
 
-    fn get_context(predecessor: &str) -> VMContextBuilder {
-        let mut builder = VMContextBuilder::new();
-        builder.predecessor_account_id(predecessor.parse().unwrap());
-        builder.current_account_id("dns.alice.testnet".parse().unwrap());
-        builder
+    /// Replace one record, matched by type and value, with `new`
+    ///
+    /// Unlike reading the records and sending them back with `dns_update`,
+    /// this can't undo a concurrent change to the other records.
+    ///
+    /// Attach a deposit for any storage added (see `dns_storage_cost`); any
+    /// excess is refunded.
+    ///
+    /// # Panics
+    /// * If caller is not allowed to edit this name and type
+    /// * If `new` is invalid or of a different type than `old`
+    /// * If the name has no record matching `old`, or already has `new`
+    /// * If the attached deposit doesn't cover the added storage
+    #[payable]
+    pub fn dns_replace_record(&mut self, name: String, old: DnsRecord, new: DnsRecord) {
+        let operation = Operation::ReplaceRecord { name, old, new }
+            .validated()
+            .unwrap_or_else(|reason| env::panic_str(&reason));
+        self.apply_changes(vec![operation]);
+    }
+
+    /// Apply several record changes atomically, in order
+    ///
+    /// Every operation is validated before anything is written, and if any
+    /// operation fails none of them take effect. All resulting events share
+    /// one new zone serial.
+    ///
+    /// # Arguments
+    /// * `operations` - `add`, `replace`, `delete`, `remove` and
+    ///   `replace_record` operations (at most 100)
+    /// * `expected_serial` - If given, the call fails unless the zone serial
+    ///   still has this value (compare-and-swap)
+    ///
+    /// # Panics
+    /// * If the zone serial doesn't match `expected_serial`
+    /// * If any operation is invalid or not permitted for the caller
+    /// * If the attached deposit doesn't cover the added storage
+    #[payable]
+    pub fn dns_apply(&mut self, operations: Vec<Operation>, expected_serial: Option<U64>) {
+        if let Some(expected) = expected_serial {
+            assert_eq!(
+                expected.0, self.serial,
+                "Zone serial is {}, expected {}",
+                self.serial, expected.0
+            );
+        }
+        assert!(!operations.is_empty(), "No operations to apply");
+        assert!(
+            operations.len() <= MAX_OPERATIONS,
+            "At most {} operations can be applied at once",
+            MAX_OPERATIONS
+        );
+
+        let operations = operations
+            .into_iter()
+            .enumerate()
+            .map(|(i, operation)| {
+                operation.validated().unwrap_or_else(|reason| {
+                    env::panic_str(&format!("Operation {}: {}", i, reason))
+                })
+            })
+            .collect();
+        self.apply_changes(operations);
+    }
+
+    /// Remove records whose `valid_until` has passed, a page of record sets
+    /// at a time
+    ///
+    /// Anyone can call this; no deposit is taken, and the balance locked for
+    /// the released storage is refunded to the owner. Removals share one new
+    /// zone serial and emit the usual update and delete events.
+    ///
+    /// # Arguments
+    /// * `from_index` - Storage index of the first record set to examine
+    /// * `limit` - Record sets to examine (default 100, at most 500)
+    ///
+    /// # Returns
+    /// * The number of records removed
+    pub fn dns_remove_expired(&mut self, from_index: Option<u32>, limit: Option<u32>) -> u32 {
+        let now = env::block_timestamp();
+        let (start, end, _) = page_range(from_index, limit, self.records.len());
+
+        let mut removed = 0;
+        let mut operations = Vec::new();
+        for (key, records) in self
+            .records
+            .iter()
+            .skip(start as usize)
+            .take((end - start) as usize)
+        {
+            let kept: Vec<DnsRecord> =
+                records.iter().filter(|r| !r.is_expired(now)).cloned().collect();
+            if kept.len() == records.len() {
+                continue;
+            }
+            removed += (records.len() - kept.len()) as u32;
+            let name = key.name.clone();
+            operations.push(if kept.is_empty() {
+                Operation::Delete {
+                    name,
+                    record_type: Some(key.record_type.clone()),
+                }
+            } else {
+                Operation::Replace { name, records: kept }
+            });
+        }
+
+        if !operations.is_empty() {
+            self.commit_changes(operations, false);
+        }
+        removed
+    }
+
+    /// Keep the last `change_sets` change sets for point-in-time queries
+    /// (owner only, at most 1000)
+    ///
+    /// Each change set is charged to whoever makes the change, as part of its
+    /// storage deposit. 0 turns history off and deletes what was kept; the
+    /// storage released by pruning is refunded to the owner.
+    pub fn dns_set_history_retention(&mut self, change_sets: u32) {
+        self.assert_owner();
+        assert!(
+            change_sets <= MAX_HISTORY_RETENTION,
+            "History retention can be at most {} change sets",
+            MAX_HISTORY_RETENTION
+        );
+        let initial_usage = env::storage_usage();
+        self.history_retention = change_sets;
+        self.prune_history();
+        self.settle_storage(initial_usage);
+    }
+
+    /// Make an account an admin (owner only)
+    #[payable]
+    pub fn dns_add_admin(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        let initial_usage = env::storage_usage();
+        if self.admins.insert(account_id.clone()) {
+            DnsEvent::AdminAdded { account_id }.emit();
+        }
+        self.settle_storage(initial_usage);
+    }
+
+    /// Remove an admin (owner only)
+    #[payable]
+    pub fn dns_remove_admin(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        let initial_usage = env::storage_usage();
+        if self.admins.remove(&account_id) {
+            DnsEvent::AdminRemoved { account_id }.emit();
+        }
+        self.settle_storage(initial_usage);
+    }
+
+    /// Allow an editor to change the names and record types a grant covers
+    ///
+    /// # Arguments
+    /// * `account_id` - The editor
+    /// * `grant` - Name pattern (`www`, `*`, `_acme-challenge.*`, `*.staging`)
+    ///   and record types (empty for all)
+    ///
+    /// # Panics
+    /// * If caller is not the owner or an admin
+    /// * If the pattern or a record type is invalid
+    #[payable]
+    pub fn dns_grant(&mut self, account_id: AccountId, grant: Grant) {
+        self.assert_admin();
+        let initial_usage = env::storage_usage();
+        let grant = grant.normalized().unwrap_or_else(|reason| env::panic_str(&reason));
+
+        let grants = self.editors.entry(account_id.clone()).or_default();
+        if !grants.contains(&grant) {
+            grants.push(grant.clone());
+            DnsEvent::GrantAdded { account_id, grant }.emit();
+        }
+        self.settle_storage(initial_usage);
+    }
+
+    /// Revoke an editor's grants for a pattern, or all of them when `pattern`
+    /// is `None`
+    #[payable]
+    pub fn dns_revoke(&mut self, account_id: AccountId, pattern: Option<String>) {
+        self.assert_admin();
+        let initial_usage = env::storage_usage();
+
+        let pattern = pattern.map(|pattern| {
+            Grant {
+                pattern,
+                record_types: Vec::new(),
+            }
+            .normalized()
+            .unwrap_or_else(|reason| env::panic_str(&reason))
+            .pattern
+        });
+
+        let revoked = match &pattern {
+            Some(pattern) => {
+                let grants = self.editors.get_mut(&account_id);
+                let revoked = grants.is_some_and(|grants| {
+                    let before = grants.len();
+                    grants.retain(|g| g.pattern != *pattern);
+                    grants.len() != before
+                });
+                if self.editors.get(&account_id).is_some_and(|grants| grants.is_empty()) {
+                    self.editors.remove(&account_id);
+                }
+                revoked
+            }
+            None => self.editors.remove(&account_id).is_some(),
+        };
+        if revoked {
+            DnsEvent::GrantRevoked { account_id, pattern }.emit();
+        }
+        self.settle_storage(initial_usage);
+    }
+
+    /// Propose transferring ownership to a new account
+    ///
+    /// Nothing changes until `new_owner` calls `accept_ownership`, which it
+    /// must do within 7 days. A new proposal replaces any pending one.
+    /// 
+    /// # Arguments
+    /// * `new_owner` - The new owner account ID
+    pub fn transfer_ownership(&mut self, new_owner: AccountId) {
+        self.assert_owner();
+        assert_ne!(new_owner, self.owner, "{} already owns this zone", new_owner);
+        let pending = PendingTransfer {
+            new_owner: new_owner.clone(),
+            expires_at: (env::block_timestamp() + OWNERSHIP_TRANSFER_TTL_NS).into(),
+        };
+        DnsEvent::OwnershipTransferProposed {
+            owner: self.owner.clone(),
+            new_owner,
+            expires_at: pending.expires_at,
+        }
+        .emit();
+        self.pending_owner = Some(pending);
+    }
+
+    /// Accept a pending ownership transfer (proposed new owner only)
+    pub fn accept_ownership(&mut self) {
+        let caller = env::predecessor_account_id();
+        let pending = self
+            .pending_owner
+            .take()
+            .unwrap_or_else(|| env::panic_str("No ownership transfer is pending"));
+        assert_eq!(
+            caller, pending.new_owner,
+            "Only the proposed owner ({}) can accept the transfer",
+            pending.new_owner
+        );
+        assert!(
+            !pending.is_expired(env::block_timestamp()),
+            "The ownership transfer proposal has expired"
+        );
+
+        let old_owner = std::mem::replace(&mut self.owner, caller);
+        DnsEvent::OwnershipTransferred {
+            old_owner,
+            new_owner: self.owner.clone(),
+        }
+        .emit();
+    }
+
+    /// Cancel a pending ownership transfer (owner, or the proposed owner to
+    /// decline it)
+    pub fn cancel_ownership_transfer(&mut self) {
+        let caller = env::predecessor_account_id();
+        let pending = self
+            .pending_owner
+            .take()
+            .unwrap_or_else(|| env::panic_str("No ownership transfer is pending"));
+        assert!(
+            caller == self.owner || caller == pending.new_owner,
+            "Only the owner or the proposed owner can cancel the transfer"
+        );
+        DnsEvent::OwnershipTransferCancelled {
+            owner: self.owner.clone(),
+            new_owner: pending.new_owner,
+        }
+        .emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, NearToken};
+
+    fn get_context(predecessor: &str) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.current_account_id("dns.alice.testnet".parse().unwrap());
+        builder.attached_deposit(NearToken::from_near(1));
+        builder
+    }
+
+    /// A record with a 300 second TTL and no priority or validity window
+    pub(crate) fn record(record_type: &str, value: &str) -> DnsRecord {
+        DnsRecord {
+            record_type: record_type.to_string(),
+            value: value.to_string(),
+            ttl: 300,
+            priority: None,
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+
+    /// A record that is only served from `valid_from` until `valid_until`
+    /// (block timestamps in nanoseconds)
+    pub(crate) fn timed_record(
+        record_type: &str,
+        value: &str,
+        valid_from: Option<u64>,
+        valid_until: Option<u64>,
+    ) -> DnsRecord {
+        DnsRecord {
+            valid_from: valid_from.map(U64),
+            valid_until: valid_until.map(U64),
+            ..record(record_type, value)
+        }
     }
 
     #[test]
@@ -231,6 +1479,18 @@ mod tests {
         
         let contract = DnsContract::new();
         assert_eq!(contract.get_owner().as_str(), "alice.testnet");
+        assert_eq!(contract.get_version().state_version, STATE_VERSION);
+        assert!(contract.dns_capabilities().contains(&"resolve".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner (alice.testnet) can call this method")]
+    fn test_upgrade_owner_only() {
+        testing_env!(get_context("alice.testnet").build());
+        let contract = DnsContract::new();
+
+        testing_env!(get_context("bob.testnet").build());
+        contract.upgrade().detach();
     }
 
     #[test]
@@ -241,12 +1501,7 @@ mod tests {
         let mut contract = DnsContract::new();
         
         // Update A record
-        let records = vec![DnsRecord {
-            record_type: "A".to_string(),
-            value: "192.168.1.1".to_string(),
-            ttl: 300,
-            priority: None,
-        }];
+        let records = vec![record("A", "192.168.1.1")];
         contract.dns_update("@".to_string(), records.clone());
         
         // Query should return the record
@@ -263,20 +1518,10 @@ mod tests {
         let mut contract = DnsContract::new();
         
         // Add first A record
-        contract.dns_add("@".to_string(), DnsRecord {
-            record_type: "A".to_string(),
-            value: "192.168.1.1".to_string(),
-            ttl: 300,
-            priority: None,
-        });
+        contract.dns_add("@".to_string(), record("A", "192.168.1.1"));
         
         // Add second A record
-        contract.dns_add("@".to_string(), DnsRecord {
-            record_type: "A".to_string(),
-            value: "192.168.1.2".to_string(),
-            ttl: 300,
-            priority: None,
-        });
+        contract.dns_add("@".to_string(), record("A", "192.168.1.2"));
         
         // Should have 2 records
         let result = contract.dns_query("@".to_string(), "A".to_string());
@@ -291,18 +1536,8 @@ mod tests {
         let mut contract = DnsContract::new();
         
         // Add records
-        contract.dns_add("@".to_string(), DnsRecord {
-            record_type: "A".to_string(),
-            value: "192.168.1.1".to_string(),
-            ttl: 300,
-            priority: None,
-        });
-        contract.dns_add("@".to_string(), DnsRecord {
-            record_type: "TXT".to_string(),
-            value: "Hello".to_string(),
-            ttl: 300,
-            priority: None,
-        });
+        contract.dns_add("@".to_string(), record("A", "192.168.1.1"));
+        contract.dns_add("@".to_string(), record("TXT", "Hello"));
         
         // Delete only A records
         contract.dns_delete("@".to_string(), Some("A".to_string()));
@@ -310,6 +1545,12 @@ mod tests {
         // A should be gone, TXT should remain
         assert!(contract.dns_query("@".to_string(), "A".to_string()).is_none());
         assert!(contract.dns_query("@".to_string(), "TXT".to_string()).is_some());
+        assert_eq!(contract.dns_query_all("@".to_string()).len(), 1);
+        
+        // Deleting without a type removes everything left, including the name
+        contract.dns_delete("@".to_string(), None);
+        assert!(contract.dns_query_all("@".to_string()).is_empty());
+        assert!(contract.dns_list_names().is_empty());
     }
 
     #[test]
@@ -325,12 +1566,7 @@ mod tests {
         testing_env!(context.build());
         
         // This should panic
-        contract.dns_update("@".to_string(), vec![DnsRecord {
-            record_type: "A".to_string(),
-            value: "192.168.1.1".to_string(),
-            ttl: 300,
-            priority: None,
-        }]);
+        contract.dns_update("@".to_string(), vec![record("A", "192.168.1.1")]);
     }
 
     #[test]
@@ -341,18 +1577,327 @@ mod tests {
         let mut contract = DnsContract::new();
         
         // Add wildcard A record
-        contract.dns_add("*".to_string(), DnsRecord {
-            record_type: "A".to_string(),
-            value: "192.168.1.1".to_string(),
-            ttl: 300,
-            priority: None,
-        });
+        contract.dns_add("*".to_string(), record("A", "192.168.1.1"));
         
         // Query wildcard
         let result = contract.dns_query("*".to_string(), "A".to_string());
         assert!(result.is_some());
     }
 
+    #[test]
+    #[should_panic(expected = "Invalid A record value 'not-an-ip'")]
+    fn test_rejects_invalid_record() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        
+        let mut contract = DnsContract::new();
+        contract.dns_add("@".to_string(), record("A", "not-an-ip"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid TTL 0")]
+    fn test_rejects_zero_ttl() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        
+        let mut contract = DnsContract::new();
+        let record = DnsRecord { ttl: 0, ..record("TXT", "hello") };
+        contract.dns_update("@".to_string(), vec![record]);
+    }
+
+    #[test]
+    fn test_record_type_is_normalized() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        
+        let mut contract = DnsContract::new();
+        let mx = DnsRecord {
+            ttl: 3600,
+            priority: Some(5),
+            ..record("mx", "mx1.example.com.")
+        };
+        contract.dns_add("mail".to_string(), mx);
+        
+        let result = contract.dns_query("mail".to_string(), "MX".to_string()).unwrap();
+        assert_eq!(result[0].record_type, "MX");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid name 'a:b'")]
+    fn test_rejects_invalid_name() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        
+        let mut contract = DnsContract::new();
+        contract.dns_add("a:b".to_string(), record("A", "192.168.1.1"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach at least")]
+    fn test_requires_storage_deposit() {
+        let mut context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).build());
+        contract.dns_add("@".to_string(), record("A", "192.168.1.1"));
+    }
+
+    #[test]
+    fn test_storage_cost_estimate() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        let record = record("A", "192.168.1.1");
+        
+        let estimate = contract.dns_storage_cost("www".to_string(), vec![record.clone()], None);
+        let before = env::storage_usage();
+        contract.dns_add("www".to_string(), record.clone());
+        assert_eq!(estimate.bytes, (env::storage_usage() - before) as i64);
+        
+        // Replacing with the same record costs nothing more
+        let estimate = contract.dns_storage_cost("www".to_string(), vec![record], None);
+        assert_eq!(estimate.bytes, 0);
+        assert!(estimate.deposit.is_zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach at least")]
+    fn test_history_is_paid_by_caller() {
+        let mut context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_set_history_retention(10);
+        contract.dns_add("www".to_string(), record("A", "10.0.0.1"));
+
+        // A same-size replacement only adds its change set, which the
+        // estimate covers and the caller pays for,
+        let estimate = contract.dns_storage_cost("www".to_string(), vec![record("A", "10.0.0.2")], None);
+        let before = env::storage_usage();
+        contract.dns_update("www".to_string(), vec![record("A", "10.0.0.2")]);
+        let used = (env::storage_usage() - before) as i64;
+        assert!(used > 0);
+        assert!(estimate.bytes >= used);
+
+        // so a change needs a deposit even when its records don't grow
+        testing_env!(context.attached_deposit(NearToken::from_yoctonear(0)).build());
+        contract.dns_update("www".to_string(), vec![record("A", "10.0.0.3")]);
+    }
+
+    fn acme_grant() -> Grant {
+        Grant {
+            pattern: "_acme-challenge.*".to_string(),
+            record_types: vec!["TXT".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_editor_grant() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_grant("certbot.testnet".parse().unwrap(), acme_grant());
+        assert_eq!(
+            contract.dns_list_grants(None),
+            vec![("certbot.testnet".parse().unwrap(), vec![acme_grant()])]
+        );
+        
+        testing_env!(get_context("certbot.testnet").build());
+        let token = DnsRecord { ttl: 60, ..record("TXT", "token") };
+        contract.dns_update("_acme-challenge.www".to_string(), vec![token]);
+        contract.dns_delete("_acme-challenge.www".to_string(), None);
+        
+        testing_env!(get_context("alice.testnet").build());
+        contract.dns_revoke("certbot.testnet".parse().unwrap(), Some("_ACME-challenge.*".to_string()));
+        assert!(contract.dns_list_grants(None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "editor granted A on 'www'")]
+    fn test_editor_outside_grant() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_grant("certbot.testnet".parse().unwrap(), acme_grant());
+        
+        testing_env!(get_context("certbot.testnet").build());
+        contract.dns_add("www".to_string(), record("A", "192.168.1.1"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner (alice.testnet) can call this method")]
+    fn test_admin_manages_editors_only() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_add_admin("infra.testnet".parse().unwrap());
+        assert_eq!(contract.dns_list_admins().len(), 1);
+        
+        testing_env!(get_context("infra.testnet").build());
+        contract.dns_grant("certbot.testnet".parse().unwrap(), acme_grant());
+        // Admins can't appoint other admins
+        contract.dns_add_admin("mallory.testnet".parse().unwrap());
+    }
+
+    #[test]
+    fn test_two_step_ownership_transfer() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        
+        contract.transfer_ownership("bob.testnet".parse().unwrap());
+        assert_eq!(contract.get_owner().as_str(), "alice.testnet");
+        assert_eq!(
+            contract.get_pending_owner().unwrap().new_owner.as_str(),
+            "bob.testnet"
+        );
+        
+        testing_env!(get_context("bob.testnet").build());
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner().as_str(), "bob.testnet");
+        assert!(contract.get_pending_owner().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the proposed owner (bob.testnet)")]
+    fn test_accept_ownership_wrong_account() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.transfer_ownership("bob.testnet".parse().unwrap());
+        
+        testing_env!(get_context("mallory.testnet").build());
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "has expired")]
+    fn test_accept_ownership_expired() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.transfer_ownership("bob.testnet".parse().unwrap());
+        
+        let mut context = get_context("bob.testnet");
+        context.block_timestamp(OWNERSHIP_TRANSFER_TTL_NS + 1);
+        testing_env!(context.build());
+        assert!(contract.get_pending_owner().is_none());
+        contract.accept_ownership();
+    }
+
+    #[test]
+    #[should_panic(expected = "No ownership transfer is pending")]
+    fn test_cancel_ownership_transfer() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.transfer_ownership("bob.testnet".parse().unwrap());
+        contract.cancel_ownership_transfer();
+        
+        testing_env!(get_context("bob.testnet").build());
+        contract.accept_ownership();
+    }
+
+    #[test]
+    fn test_change_events_carry_serial() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        let record = record("A", "192.168.1.1");
+        contract.dns_add("www".to_string(), record.clone());
+        contract.dns_update("www".to_string(), vec![record]);
+        contract.dns_delete("www".to_string(), None);
+        // Deleting nothing doesn't advance the serial
+        contract.dns_delete("www".to_string(), None);
+        
+        let events: Vec<near_sdk::serde_json::Value> = near_sdk::test_utils::get_logs()
+            .iter()
+            .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+            .map(|json| near_sdk::serde_json::from_str(json).unwrap())
+            .collect();
+        let summary: Vec<(&str, &str)> = events
+            .iter()
+            .map(|e| (e["event"].as_str().unwrap(), e["data"][0]["serial"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("dns_record_added", "1"),
+                ("dns_records_updated", "2"),
+                ("dns_records_deleted", "3"),
+            ]
+        );
+        assert_eq!(events[1]["data"][0]["old"][0]["value"], "192.168.1.1");
+        assert_eq!(events[2]["data"][0]["name"], "www");
+    }
+
+    #[test]
+    fn test_dns_apply() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_add("old".to_string(), record("A", "192.168.1.1"));
+        
+        contract.dns_apply(
+            vec![
+                Operation::Replace { name: "www".to_string(), records: vec![record("A", "10.0.0.1")] },
+                Operation::Add { name: "www".to_string(), record: record("AAAA", "2001:db8::1") },
+                Operation::Add { name: "api".to_string(), record: record("CNAME", "www") },
+                Operation::Delete { name: "old".to_string(), record_type: None },
+            ],
+            Some(U64(1)),
+        );
+        
+        assert_eq!(contract.dns_query_all("www".to_string()).len(), 2);
+        assert!(contract.dns_query("api".to_string(), "CNAME".to_string()).is_some());
+        assert!(contract.dns_query_all("old".to_string()).is_empty());
+        assert_eq!(contract.dns_zone_serial(), U64(2));
+    }
+
+    #[test]
+    fn test_dns_zone_info() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        assert_eq!(contract.dns_zone_serial(), U64(0));
+        contract.dns_add("www".to_string(), record("A", "192.168.1.1"));
+        contract.dns_add("www".to_string(), record("AAAA", "2001:db8::1"));
+        contract.dns_add("@".to_string(), record("TXT", "v=spf1 -all"));
+
+        let info = contract.dns_zone_info();
+        assert_eq!(info.contract_id.as_str(), "dns.alice.testnet");
+        assert_eq!(info.owner.as_str(), "alice.testnet");
+        assert_eq!(info.serial, U64(3));
+        assert_eq!(info.names, 2);
+        assert_eq!(info.record_sets, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Zone serial is 1, expected 0")]
+    fn test_dns_apply_serial_mismatch() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        let record = record("A", "192.168.1.1");
+        contract.dns_add("www".to_string(), record.clone());
+        contract.dns_apply(vec![Operation::Add { name: "api".to_string(), record }], Some(U64(0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Operation 1: Invalid AAAA record value")]
+    fn test_dns_apply_validates_all_operations() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_apply(
+            vec![
+                Operation::Add { name: "www".to_string(), record: record("A", "10.0.0.1") },
+                Operation::Add { name: "www".to_string(), record: record("AAAA", "10.0.0.1") },
+            ],
+            None,
+        );
+    }
+
     #[test]
     fn test_dns_list_names() {
         let context = get_context("alice.testnet");
@@ -361,28 +1906,434 @@ mod tests {
         let mut contract = DnsContract::new();
         
         // Add records for different names
-        contract.dns_add("@".to_string(), DnsRecord {
-            record_type: "A".to_string(),
-            value: "192.168.1.1".to_string(),
-            ttl: 300,
-            priority: None,
-        });
-        contract.dns_add("www".to_string(), DnsRecord {
-            record_type: "A".to_string(),
-            value: "192.168.1.2".to_string(),
-            ttl: 300,
-            priority: None,
-        });
-        contract.dns_add("@".to_string(), DnsRecord {
-            record_type: "TXT".to_string(),
-            value: "test".to_string(),
-            ttl: 300,
-            priority: None,
-        });
+        contract.dns_add("@".to_string(), record("A", "192.168.1.1"));
+        contract.dns_add("www".to_string(), record("A", "192.168.1.2"));
+        contract.dns_add("@".to_string(), record("TXT", "test"));
         
         let names = contract.dns_list_names();
-        assert_eq!(names.len(), 2); // "@" and "www"
-        assert!(names.contains(&"@".to_string()));
-        assert!(names.contains(&"www".to_string()));
+        assert_eq!(names, vec!["@".to_string(), "www".to_string()]);
+    }
+
+    #[test]
+    fn test_dns_resolve_wildcards() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_add("*".to_string(), record("A", "10.0.0.1"));
+        contract.dns_add("api".to_string(), record("TXT", "api"));
+        contract.dns_add("*.api".to_string(), record("A", "10.0.0.2"));
+        contract.dns_add("www".to_string(), record("AAAA", "2001:db8::1"));
+        let resolve = |name: &str, record_type: &str| contract.dns_resolve(name.to_string(), record_type.to_string());
+
+        // Existing names never fall through to a wildcard
+        let www = resolve("WWW", "a").unwrap();
+        assert_eq!(www.name, "www");
+        assert!(www.records.is_empty());
+        assert!(www.other_types);
+
+        let host = resolve("host", "A").unwrap();
+        assert_eq!(host.name, "*");
+        assert_eq!(host.records[0].value, "10.0.0.1");
+
+        // The closest encloser of x.api is api, so only *.api applies
+        let deep = resolve("deep.x.api", "A").unwrap();
+        assert_eq!(deep.name, "*.api");
+        assert_eq!(deep.records[0].value, "10.0.0.2");
+
+        // The apex always exists
+        let apex = resolve("@", "A").unwrap();
+        assert!(apex.records.is_empty());
+        assert!(!apex.other_types);
+
+        contract.dns_delete("*".to_string(), None);
+        assert!(contract.dns_resolve("host".to_string(), "A".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_dns_resolve_empty_non_terminals() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        let record = record("A", "10.0.0.1");
+        contract.dns_add("*".to_string(), record.clone());
+
+        let estimate = contract.dns_storage_cost("x.a.b".to_string(), vec![record.clone()], None);
+        let before = env::storage_usage();
+        contract.dns_add("x.a.b".to_string(), record.clone());
+        assert_eq!(estimate.bytes, (env::storage_usage() - before) as i64);
+        contract.dns_add("y.a.b".to_string(), record);
+
+        // b and a.b exist without records, so the wildcard doesn't cover them
+        // or the names below them
+        for name in ["b", "a.b"] {
+            let ent = contract.dns_resolve(name.to_string(), "A".to_string()).unwrap();
+            assert_eq!(ent.name, name);
+            assert!(ent.records.is_empty());
+            assert!(!ent.other_types);
+        }
+        assert!(contract.dns_resolve("z.b".to_string(), "A".to_string()).is_none());
+        assert!(contract.dns_resolve("z.a.b".to_string(), "A".to_string()).is_none());
+        assert_eq!(contract.dns_resolve("c".to_string(), "A".to_string()).unwrap().name, "*");
+
+        // They stop existing with the last name below them
+        contract.dns_delete("x.a.b".to_string(), None);
+        assert!(contract.dns_resolve("a.b".to_string(), "A".to_string()).unwrap().records.is_empty());
+        contract.dns_delete("y.a.b".to_string(), None);
+        assert_eq!(contract.dns_resolve("b".to_string(), "A".to_string()).unwrap().name, "*");
+        assert_eq!(contract.dns_resolve("z.a.b".to_string(), "A".to_string()).unwrap().name, "*");
+    }
+
+    #[test]
+    fn test_batch_queries() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_add("www".to_string(), record("A", "192.168.1.1"));
+        contract.dns_add("www".to_string(), record("AAAA", "2001:db8::1"));
+        contract.dns_add("*".to_string(), record("A", "10.0.0.1"));
+        let query = |name: &str, record_type: &str| RecordQuery {
+            name: name.to_string(),
+            record_type: record_type.to_string(),
+        };
+        let queries = vec![query("www", "A"), query("www", "AAAA"), query("host", "A"), query("www", "MX")];
+
+        let results = contract.dns_query_many(queries.clone());
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap()[0].value, "192.168.1.1");
+        assert_eq!(results[1].as_ref().unwrap()[0].value, "2001:db8::1");
+        assert!(results[2].is_none());
+        assert!(results[3].is_none());
+
+        let resolved = contract.dns_resolve_many(queries);
+        assert_eq!(resolved[2].as_ref().unwrap().name, "*");
+        assert!(resolved[3].as_ref().unwrap().records.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "At most 50 queries can be made in one call")]
+    fn test_batch_query_limit() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let contract = DnsContract::new();
+        let queries = (0..51)
+            .map(|i| RecordQuery { name: format!("host{}", i), record_type: "A".to_string() })
+            .collect();
+        contract.dns_query_many(queries);
+    }
+
+    #[test]
+    fn test_history_is_off_by_default() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_add("www".to_string(), record("A", "192.168.1.1"));
+
+        let info = contract.dns_history_info();
+        assert_eq!(info.retention, 0);
+        assert_eq!(info.oldest_serial, U64(1));
+        assert!(contract.dns_changes_since(U64(1), None).is_empty());
+        assert_eq!(contract.dns_records_at("www".to_string(), U64(1)).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "History is only kept back to serial 1")]
+    fn test_history_before_retention() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_add("www".to_string(), record("A", "192.168.1.1"));
+        contract.dns_records_at("www".to_string(), U64(0));
+    }
+
+    #[test]
+    fn test_point_in_time_queries() {
+        let mut context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_set_history_retention(2);
+
+        // serial 1 at block 10, 2 at block 20, 3 at block 30
+        for (block, value) in [(10, "10.0.0.1"), (20, "10.0.0.2"), (30, "10.0.0.3")] {
+            testing_env!(context.block_height(block).build());
+            contract.dns_update("www".to_string(), vec![record("A", value)]);
+        }
+
+        // Only the last two change sets are kept
+        assert_eq!(contract.dns_history_info().oldest_serial, U64(1));
+        let changes = contract.dns_changes_since(U64(1), None);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].serial, U64(2));
+        assert_eq!(changes[0].block_height, U64(20));
+        assert_eq!(changes[0].changes[0].old[0].value, "10.0.0.1");
+        assert_eq!(changes[0].changes[0].new[0].value, "10.0.0.2");
+
+        let at = |serial: u64| contract.dns_records_at("www".to_string(), U64(serial));
+        assert_eq!(at(1)[0].records[0].value, "10.0.0.1");
+        assert_eq!(at(2)[0].records[0].value, "10.0.0.2");
+        assert_eq!(at(3)[0].records[0].value, "10.0.0.3");
+
+        assert_eq!(contract.dns_serial_at_block(U64(25)), Some(U64(2)));
+        assert_eq!(contract.dns_serial_at_block(U64(30)), Some(U64(3)));
+        assert_eq!(contract.dns_serial_at_block(U64(15)), None);
+
+        // A delete is kept as a change to no records
+        contract.dns_delete("www".to_string(), None);
+        assert!(contract.dns_records_at("www".to_string(), U64(4)).is_empty());
+        assert_eq!(contract.dns_records_at("www".to_string(), U64(3))[0].records[0].value, "10.0.0.3");
+
+        contract.dns_set_history_retention(0);
+        assert_eq!(contract.dns_history_info().oldest_serial, U64(4));
+    }
+
+    #[test]
+    fn test_list_pages() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        for i in 0..5 {
+            contract.dns_add(format!("host{}", i), record("A", &format!("10.0.0.{}", i)));
+        }
+        contract.dns_add("_acme-challenge".to_string(), record("TXT", "token"));
+        contract.dns_add("host0".to_string(), record("AAAA", "2001:db8::1"));
+        assert_eq!(contract.dns_count_names(), 6);
+        assert_eq!(contract.dns_count_record_sets(), 7);
+
+        // Walking the pages returns every name once, in sorted order
+        let walk = |contract: &DnsContract| {
+            let mut names = Vec::new();
+            let mut start_after = None;
+            loop {
+                let page = contract.dns_list_names_page(start_after, Some(4), None);
+                names.extend(page.items);
+                match page.next {
+                    Some(next) => start_after = Some(next),
+                    None => break,
+                }
+            }
+            names
+        };
+        assert_eq!(walk(&contract), contract.dns_list_names());
+        assert!(contract.dns_list_names().is_sorted());
+
+        // Removing a name doesn't move the ones after it across the cursor
+        let first = contract.dns_list_names_page(None, Some(2), None);
+        assert_eq!(first.items, vec!["_acme-challenge".to_string(), "host0".to_string()]);
+        contract.dns_delete("_acme-challenge".to_string(), None);
+        let second = contract.dns_list_names_page(first.next, Some(2), None);
+        assert_eq!(second.items, vec!["host1".to_string(), "host2".to_string()]);
+        assert_eq!(walk(&contract).len(), 5);
+
+        // A prefix filter fills the page with matching names
+        contract.dns_add("_acme-challenge".to_string(), record("TXT", "token"));
+        let hosts = contract.dns_list_names_page(None, Some(3), Some("HOST".to_string()));
+        assert_eq!(hosts.items, vec!["host0".to_string(), "host1".to_string(), "host2".to_string()]);
+        assert_eq!(hosts.next, Some("host2".to_string()));
+        let acme = contract.dns_list_names_page(None, None, Some("_ACME".to_string()));
+        assert_eq!(acme.items, vec!["_acme-challenge".to_string()]);
+        assert_eq!(acme.next, None);
+
+        let aaaa = contract.dns_list_records_page(None, None, Some("host".to_string()), Some("aaaa".to_string()));
+        assert_eq!(aaaa.items.len(), 1);
+        assert_eq!(aaaa.items[0].name, "host0");
+        assert_eq!(aaaa.items[0].records[0].value, "2001:db8::1");
+    }
+
+    #[test]
+    fn test_time_bounded_records() {
+        let mut context = get_context("alice.testnet");
+        testing_env!(context.block_timestamp(1_000).build());
+        let mut contract = DnsContract::new();
+        contract.dns_update(
+            "_acme-challenge".to_string(),
+            vec![
+                timed_record("TXT", "current", None, Some(2_000)),
+                timed_record("TXT", "next", Some(2_000), None),
+            ],
+        );
+
+        let query = |contract: &DnsContract| {
+            contract
+                .dns_query("_acme-challenge".to_string(), "TXT".to_string())
+                .map(|records| records.into_iter().map(|r| r.value).collect::<Vec<_>>())
+        };
+        assert_eq!(query(&contract), Some(vec!["current".to_string()]));
+
+        testing_env!(context.block_timestamp(2_000).build());
+        assert_eq!(query(&contract), Some(vec!["next".to_string()]));
+        let resolution = contract
+            .dns_resolve("_acme-challenge".to_string(), "TXT".to_string())
+            .unwrap();
+        assert_eq!(resolution.records.len(), 1);
+        assert_eq!(contract.dns_query_all("_acme-challenge".to_string()).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "valid_until is in the past")]
+    fn test_rejects_expired_record() {
+        let mut context = get_context("alice.testnet");
+        testing_env!(context.block_timestamp(1_000).build());
+        let mut contract = DnsContract::new();
+        contract.dns_add("www".to_string(), timed_record("TXT", "old", None, Some(1_000)));
+    }
+
+    #[test]
+    fn test_remove_expired() {
+        let mut context = get_context("alice.testnet");
+        testing_env!(context.block_timestamp(1_000).build());
+        let mut contract = DnsContract::new();
+        contract.dns_update(
+            "www".to_string(),
+            vec![
+                timed_record("TXT", "old", None, Some(2_000)),
+                timed_record("TXT", "new", None, None),
+            ],
+        );
+        contract.dns_add("_acme-challenge".to_string(), timed_record("TXT", "token", None, Some(2_000)));
+        assert_eq!(contract.dns_remove_expired(None, None), 0);
+        assert_eq!(contract.dns_zone_serial(), U64(2));
+
+        // Anyone can clean up, without a deposit
+        let mut context = get_context("bob.testnet");
+        context.attached_deposit(NearToken::from_yoctonear(0));
+        testing_env!(context.block_timestamp(2_000).build());
+        assert_eq!(contract.dns_remove_expired(None, None), 2);
+        assert_eq!(contract.dns_zone_serial(), U64(3));
+        assert_eq!(contract.dns_list_names(), vec!["www".to_string()]);
+        assert_eq!(
+            contract.dns_list_records_page(None, None, None, None).items[0].records,
+            vec![timed_record("TXT", "new", None, None)]
+        );
+    }
+
+    #[test]
+    fn test_remove_and_replace_record() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        let values = |contract: &DnsContract| {
+            contract
+                .dns_query("www".to_string(), "A".to_string())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|r| r.value)
+                .collect::<Vec<_>>()
+        };
+        for value in ["10.0.0.1", "10.0.0.2", "10.0.0.3"] {
+            contract.dns_add("www".to_string(), record("A", value));
+        }
+
+        // Matching ignores the TTL
+        let mut stale = record("A", "10.0.0.2");
+        stale.ttl = 60;
+        contract.dns_remove_record("www".to_string(), stale);
+        assert_eq!(values(&contract), vec!["10.0.0.1", "10.0.0.3"]);
+
+        contract.dns_replace_record("www".to_string(), record("A", "10.0.0.3"), record("A", "10.0.0.4"));
+        assert_eq!(values(&contract), vec!["10.0.0.1", "10.0.0.4"]);
+
+        // Removing the last record deletes the type
+        contract.dns_remove_record("www".to_string(), record("A", "10.0.0.1"));
+        contract.dns_remove_record("www".to_string(), record("A", "10.0.0.4"));
+        assert!(contract.dns_list_names().is_empty());
+        assert_eq!(contract.dns_zone_serial(), U64(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "'www' already has the A record '10.0.0.1'")]
+    fn test_dns_add_rejects_duplicate() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        let record = record("A", "10.0.0.1");
+        contract.dns_add("www".to_string(), record.clone());
+        contract.dns_add("www".to_string(), DnsRecord { ttl: 60, ..record });
+    }
+
+    #[test]
+    #[should_panic(expected = "'www' has no A record '10.0.0.9'")]
+    fn test_remove_missing_record() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_remove_record(
+            "www".to_string(),
+            record("a", "10.0.0.9"),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "'www' can't have a CNAME and A records at the same time")]
+    fn test_cname_cannot_join_other_records() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_update("www".to_string(), vec![record("CNAME", "web.example.com.")]);
+        contract.dns_add(
+            "www".to_string(),
+            record("A", "10.0.0.1"),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The zone apex '@' can't have a CNAME record")]
+    fn test_no_cname_at_apex() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        contract.dns_add("@".to_string(), record("CNAME", "web.example.com."));
+    }
+
+    #[test]
+    fn test_swap_records_for_cname() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        let a = record("A", "10.0.0.1");
+        contract.dns_add("www".to_string(), a.clone());
+
+        // Rules are checked once the whole change is applied
+        contract.dns_apply(
+            vec![
+                Operation::Add { name: "www".to_string(), record: record("CNAME", "web.example.com.") },
+                Operation::Remove { name: "www".to_string(), record: a },
+            ],
+            None,
+        );
+        assert_eq!(contract.types_of("www"), vec!["CNAME".to_string()]);
+    }
+
+    #[test]
+    fn test_dns_lint() {
+        let context = get_context("alice.testnet");
+        testing_env!(context.build());
+        let mut contract = DnsContract::new();
+        // Written the way state from before the rules could look
+        contract.put_records(DnsContract::make_key("@", "CNAME"), vec![record("CNAME", "web")]);
+        contract.put_records(
+            DnsContract::make_key("www", "CNAME"),
+            vec![record("CNAME", "a"), record("CNAME", "b")],
+        );
+        contract.put_records(DnsContract::make_key("ok", "CNAME"), vec![record("CNAME", "web")]);
+
+        let mut violations = contract.dns_lint(None, None).items;
+        violations.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(
+            violations,
+            vec![
+                Violation {
+                    name: "@".to_string(),
+                    problem: "The zone apex '@' can't have a CNAME record".to_string(),
+                },
+                Violation {
+                    name: "www".to_string(),
+                    problem: "'www' can have only one CNAME record".to_string(),
+                },
+            ]
+        );
+
+        // Removing records is allowed even while a name breaks the rules
+        contract.dns_remove_record("www".to_string(), record("CNAME", "b"));
+        contract.dns_delete("@".to_string(), None);
+        assert!(contract.dns_lint(None, None).items.is_empty());
     }
 }
@@ -0,0 +1,131 @@
+use near_sdk::near;
+
+use crate::DnsRecord;
+
+/// A rule a name's records break
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub name: String,
+    pub problem: String,
+}
+
+/// Block timestamps a record is served between, from `now` on
+///
+/// Empty (`start >= end`) once the record has expired.
+fn window(record: &DnsRecord, now: u64) -> (u64, u64) {
+    let start = record.valid_from.map_or(0, |from| from.0).max(now);
+    let end = record.valid_until.map_or(u64::MAX, |until| until.0);
+    (start, end)
+}
+
+/// Whether two records are ever served at the same time from `now` on
+fn overlap(a: &DnsRecord, b: &DnsRecord, now: u64) -> bool {
+    let (a_start, a_end) = window(a, now);
+    let (b_start, b_end) = window(b, now);
+    a_start < b_end && b_start < a_end && a_start < a_end && b_start < b_end
+}
+
+/// Check one name's records against the coexistence rules of RFC 1034 and
+/// RFC 2181:
+///
+/// * a name with a CNAME has no other records, and at most one CNAME
+/// * the zone apex `@` has no CNAME
+/// * SOA records are only at `@`, and there is one at most
+///
+/// Records that are never served at the same time don't conflict, so a
+/// planned switch from an address to a CNAME with `valid_until` and
+/// `valid_from` is allowed. Records that have expired by `now` are ignored.
+pub fn check_name(name: &str, records: &[DnsRecord], now: u64) -> Vec<String> {
+    let live: Vec<&DnsRecord> = records
+        .iter()
+        .filter(|r| {
+            let (start, end) = window(r, now);
+            start < end
+        })
+        .collect();
+    let is = |r: &DnsRecord, record_type: &str| r.record_type.eq_ignore_ascii_case(record_type);
+
+    let mut problems = Vec::new();
+    let mut report = |problem: String| {
+        if !problems.contains(&problem) {
+            problems.push(problem);
+        }
+    };
+
+    for (i, a) in live.iter().enumerate() {
+        if is(a, "CNAME") && name == "@" {
+            report("The zone apex '@' can't have a CNAME record".to_string());
+        }
+        if is(a, "SOA") && name != "@" {
+            report(format!(
+                "'{}' can't have an SOA record; only the zone apex '@' can",
+                name
+            ));
+        }
+        for b in live[i + 1..].iter().filter(|b| overlap(a, b, now)) {
+            if is(a, "CNAME") && is(b, "CNAME") {
+                report(format!("'{}' can have only one CNAME record", name));
+            } else if is(a, "CNAME") || is(b, "CNAME") {
+                let other = if is(a, "CNAME") { b } else { a };
+                report(format!(
+                    "'{}' can't have a CNAME and {} records at the same time",
+                    name,
+                    other.record_type.to_uppercase()
+                ));
+            } else if is(a, "SOA") && is(b, "SOA") {
+                report(format!("'{}' can have only one SOA record", name));
+            }
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{record, timed_record};
+
+    #[test]
+    fn test_cname_rules() {
+        let cname = record("CNAME", "web.example.com.");
+        assert!(check_name("www", std::slice::from_ref(&cname), 0).is_empty());
+        assert_eq!(
+            check_name("www", &[cname.clone(), record("TXT", "x")], 0),
+            vec!["'www' can't have a CNAME and TXT records at the same time"]
+        );
+        assert_eq!(
+            check_name("www", &[cname.clone(), record("CNAME", "other")], 0),
+            vec!["'www' can have only one CNAME record"]
+        );
+        assert_eq!(
+            check_name("@", std::slice::from_ref(&cname), 0),
+            vec!["The zone apex '@' can't have a CNAME record"]
+        );
+        assert!(check_name("www", &[record("A", "10.0.0.1"), record("TXT", "x")], 0).is_empty());
+    }
+
+    #[test]
+    fn test_soa_rules() {
+        let soa = record("SOA", "ns1 hostmaster 1 7200 3600 1209600 300");
+        assert!(check_name("@", std::slice::from_ref(&soa), 0).is_empty());
+        assert_eq!(check_name("www", std::slice::from_ref(&soa), 0).len(), 1);
+        assert_eq!(
+            check_name("@", &[soa.clone(), soa], 0),
+            vec!["'@' can have only one SOA record"]
+        );
+    }
+
+    #[test]
+    fn test_records_apart_in_time_dont_conflict() {
+        // Switch from an address to a CNAME at 1000
+        let a = timed_record("A", "10.0.0.1", None, Some(1000));
+        let cname = timed_record("CNAME", "web.example.com.", Some(1000), None);
+        assert!(check_name("www", &[a.clone(), cname.clone()], 0).is_empty());
+
+        let early = timed_record("CNAME", "web.example.com.", Some(999), None);
+        assert_eq!(check_name("www", &[a.clone(), early.clone()], 0).len(), 1);
+        // Once the address has expired there is nothing to conflict with
+        assert!(check_name("www", &[a, early], 1000).is_empty());
+    }
+}
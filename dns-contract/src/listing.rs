@@ -0,0 +1,116 @@
+use near_sdk::near;
+
+use crate::DnsRecord;
+
+/// Entries examined by a page when no `limit` is given
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+/// Most entries a page examines, to stay within the view gas limit
+pub const MAX_PAGE_SIZE: u32 = 500;
+
+/// One page of a listing
+///
+/// Listings walk names in sorted order and resume after the last name of the
+/// previous page, so names added or removed between pages don't shift the
+/// rest of the listing.
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// `start_after` for the next page, or `None` after the last page
+    pub next: Option<String>,
+}
+
+/// Records of one name and type
+#[near(serializers = [json])]
+#[derive(Clone, Debug)]
+pub struct RecordSet {
+    pub name: String,
+    pub record_type: String,
+    pub records: Vec<DnsRecord>,
+}
+
+/// Names a page examines from a sorted list, and the next page's cursor
+///
+/// The page starts after `start_after` and holds only names starting with
+/// `prefix`, which sit together in sorted order.
+pub fn page_names<'a>(
+    sorted: &'a [String],
+    start_after: Option<&str>,
+    limit: Option<u32>,
+    prefix: &str,
+) -> (&'a [String], Option<String>) {
+    let after = start_after.map_or(0, |cursor| sorted.partition_point(|n| n.as_str() <= cursor));
+    let start = after.max(sorted.partition_point(|n| n.as_str() < prefix));
+    let matching = sorted[start..].partition_point(|n| n.starts_with(prefix));
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize;
+    let page = &sorted[start..start + matching.min(limit)];
+    let next = (page.len() < matching).then(|| page[page.len() - 1].clone());
+    (page, next)
+}
+
+/// Range of storage indexes a page examines, and the next page's start
+///
+/// `limit` counts entries examined, not entries returned, so a filtered page
+/// can come back short or empty before the end of the listing.
+pub fn page_range(
+    from_index: Option<u32>,
+    limit: Option<u32>,
+    len: u32,
+) -> (u32, u32, Option<u32>) {
+    let start = from_index.unwrap_or(0).min(len);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let end = start.saturating_add(limit).min(len);
+    let next_index = (end < len).then_some(end);
+    (start, end, next_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_names() {
+        let sorted: Vec<String> = ["@", "_acme-challenge", "_acme-challenge.www", "mail", "www"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        assert_eq!(
+            page_names(&sorted, None, Some(2), ""),
+            (&sorted[0..2], Some("_acme-challenge".to_string()))
+        );
+        assert_eq!(
+            page_names(&sorted, Some("_acme-challenge"), None, ""),
+            (&sorted[2..], None)
+        );
+        // A removed cursor still resumes at the next name
+        assert_eq!(
+            page_names(&sorted, Some("m"), None, ""),
+            (&sorted[3..], None)
+        );
+        assert_eq!(
+            page_names(&sorted, None, Some(1), "_acme"),
+            (&sorted[1..2], Some("_acme-challenge".to_string()))
+        );
+        assert_eq!(
+            page_names(&sorted, Some("_acme-challenge"), Some(1), "_acme"),
+            (&sorted[2..3], None)
+        );
+        assert_eq!(
+            page_names(&sorted, Some("www"), None, ""),
+            (&sorted[5..], None)
+        );
+        assert_eq!(page_names(&sorted, None, None, "zz"), (&sorted[5..], None));
+    }
+
+    #[test]
+    fn test_page_range() {
+        assert_eq!(page_range(None, None, 250), (0, 100, Some(100)));
+        assert_eq!(page_range(Some(200), None, 250), (200, 250, None));
+        assert_eq!(page_range(Some(300), Some(10), 250), (250, 250, None));
+        assert_eq!(page_range(None, Some(0), 5), (0, 1, Some(1)));
+        assert_eq!(
+            page_range(None, Some(10_000), 1000),
+            (0, MAX_PAGE_SIZE, Some(MAX_PAGE_SIZE))
+        );
+    }
+}
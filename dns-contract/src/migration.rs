@@ -0,0 +1,181 @@
+use near_sdk::borsh::BorshDeserialize;
+use near_sdk::store::{IterableMap, IterableSet, Lazy, LookupMap};
+use near_sdk::{AccountId, env, near};
+
+use crate::names::normalize_name;
+use crate::version::STATE_VERSION;
+use crate::{DnsContract, DnsRecord, RecordKey};
+
+/// Storage key near-sdk keeps the contract state under
+const STATE_KEY: &[u8] = b"STATE";
+
+/// State layout from before records were keyed by [`RecordKey`]
+#[near(serializers = [borsh])]
+pub struct LegacyDnsContract {
+    /// DNS records stored as "name:TYPE" -> Vec<LegacyDnsRecord>
+    records: IterableMap<String, Vec<LegacyDnsRecord>>,
+    owner: AccountId,
+}
+
+/// Record layout from before records had a validity window
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct LegacyDnsRecord {
+    record_type: String,
+    value: String,
+    ttl: u32,
+    priority: Option<u16>,
+}
+
+impl From<LegacyDnsRecord> for DnsRecord {
+    fn from(record: LegacyDnsRecord) -> Self {
+        DnsRecord {
+            record_type: record.record_type,
+            value: record.value,
+            ttl: record.ttl,
+            priority: record.priority,
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+}
+
+/// Read the stored state, whichever layout wrote it, and convert it to the
+/// current one
+///
+/// State that already has the current layout is returned as is. Versioned
+/// layouts older than [`STATE_VERSION`] are kept here as their own structs
+/// and tried in turn; state from before versioning keyed records by
+/// `"name:TYPE"` strings.
+pub(crate) fn migrate_state() -> DnsContract {
+    let state =
+        env::storage_read(STATE_KEY).unwrap_or_else(|| env::panic_str("No state to migrate"));
+    if let Ok(contract) = DnsContract::try_from_slice(&state) {
+        env::log_str(&format!("State is already at version {}", contract.version));
+        return contract;
+    }
+    migrate_legacy_state()
+}
+
+/// Read the legacy state, move every record to the structured key map, build
+/// the per-name type index and remove the old entries
+///
+/// Names that don't pass validation are kept as stored, so no records are
+/// lost; they can still be queried and deleted.
+pub(crate) fn migrate_legacy_state() -> DnsContract {
+    let mut legacy: LegacyDnsContract =
+        env::state_read().unwrap_or_else(|| env::panic_str("No legacy state to migrate"));
+
+    let mut contract = DnsContract {
+        version: STATE_VERSION,
+        records: IterableMap::new(b"k"),
+        names: IterableMap::new(b"n"),
+        sorted_names: Lazy::new(b"o", Vec::new()),
+        descendants: LookupMap::new(b"d"),
+        admins: IterableSet::new(b"a"),
+        editors: IterableMap::new(b"e"),
+        owner: legacy.owner.clone(),
+        pending_owner: None,
+        serial: 0,
+        history: LookupMap::new(b"h"),
+        history_retention: 0,
+        history_start: 1,
+    };
+
+    let mut migrated = 0;
+    for (key, records) in legacy.records.iter() {
+        // The type never contains ':', so the last one separates it from the name
+        let (name, record_type) = key.rsplit_once(':').unwrap_or((key.as_str(), ""));
+        let name = normalize_name(name).unwrap_or_else(|reason| {
+            env::log_str(&format!("Keeping unvalidated name: {}", reason));
+            name.to_lowercase()
+        });
+
+        let key = RecordKey {
+            name,
+            record_type: record_type.to_uppercase(),
+        };
+        let mut merged = contract.records.get(&key).cloned().unwrap_or_default();
+        merged.extend(records.iter().cloned().map(DnsRecord::from));
+        contract.put_records(key, merged);
+        migrated += 1;
+    }
+
+    legacy.records.clear();
+    legacy.records.flush();
+    contract.records.flush();
+    contract.names.flush();
+    contract.sorted_names.flush();
+    contract.descendants.flush();
+
+    env::log_str(&format!("Migrated {} record set(s)", migrated));
+    contract
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn record(record_type: &str, value: &str) -> LegacyDnsRecord {
+        LegacyDnsRecord {
+            record_type: record_type.to_string(),
+            value: value.to_string(),
+            ttl: 300,
+            priority: None,
+        }
+    }
+
+    #[test]
+    fn test_migrate_legacy_state() {
+        let mut context = VMContextBuilder::new();
+        context.current_account_id("dns.alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut legacy = LegacyDnsContract {
+            records: IterableMap::new(b"r"),
+            owner: "alice.testnet".parse().unwrap(),
+        };
+        legacy
+            .records
+            .insert("www:A".to_string(), vec![record("A", "192.168.1.1")]);
+        legacy.records.insert(
+            "_sip._tcp:SRV".to_string(),
+            vec![record("SRV", "5 5060 sip")],
+        );
+        legacy.records.flush();
+        env::state_write(&legacy);
+
+        let contract = DnsContract::migrate();
+        assert_eq!(contract.get_version().state_version, STATE_VERSION);
+        assert_eq!(contract.get_owner().as_str(), "alice.testnet");
+        assert_eq!(
+            contract.dns_list_names(),
+            vec!["_sip._tcp".to_string(), "www".to_string()]
+        );
+        assert_eq!(
+            contract
+                .dns_query("www".to_string(), "A".to_string())
+                .unwrap()[0]
+                .value,
+            "192.168.1.1"
+        );
+        assert_eq!(contract.dns_query_all("_sip._tcp".to_string()).len(), 1);
+    }
+
+    #[test]
+    fn test_migrate_current_state() {
+        let mut context = VMContextBuilder::new();
+        context.current_account_id("dns.alice.testnet".parse().unwrap());
+        testing_env!(context.build());
+
+        let mut contract = DnsContract::new();
+        contract.serial = 7;
+        env::state_write(&contract);
+
+        let migrated = DnsContract::migrate();
+        assert_eq!(migrated.dns_zone_serial().0, 7);
+        assert_eq!(migrated.get_version().state_version, STATE_VERSION);
+    }
+}
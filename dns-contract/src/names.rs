@@ -0,0 +1,217 @@
+/// Longest name accepted, in bytes
+pub const MAX_NAME_LEN: usize = 253;
+/// Longest label accepted, in bytes
+pub const MAX_LABEL_LEN: usize = 63;
+
+/// Validate a record name relative to the zone and return it in canonical
+/// (lower-case) form
+///
+/// Accepted names are `@` for the zone itself, or dot-separated labels of
+/// letters, digits and `-` (not at either end). A label may start with `_`
+/// for service names such as `_sip._tcp`, `*` is allowed only as the leftmost
+/// label, and internationalized labels must be given as valid punycode
+/// (`xn--...`).
+pub fn normalize_name(name: &str) -> Result<String, String> {
+    if name == "@" {
+        return Ok(name.to_string());
+    }
+    let invalid = |reason: &str| format!("Invalid name '{}': {}", name, reason);
+
+    if !name.is_ascii() {
+        return Err(invalid(
+            "internationalized names must be given in punycode (xn--...)",
+        ));
+    }
+    if name.is_empty() {
+        return Err(invalid("name is empty"));
+    }
+    if name.len() > MAX_NAME_LEN {
+        return Err(invalid(&format!("longer than {} bytes", MAX_NAME_LEN)));
+    }
+
+    let normalized = name.to_ascii_lowercase();
+    for (i, label) in normalized.split('.').enumerate() {
+        if label == "*" {
+            if i == 0 {
+                continue;
+            }
+            return Err(invalid("'*' is only allowed as the leftmost label"));
+        }
+        check_label(label).map_err(invalid)?;
+    }
+    Ok(normalized)
+}
+
+/// Names above `name` in the zone, nearest first and ending with `@`
+///
+/// `www.api` gives `api`, `@`; `@` itself has none.
+pub fn ancestors(name: &str) -> impl Iterator<Item = &str> {
+    let parents = name.match_indices('.').map(move |(i, _)| &name[i + 1..]);
+    let apex = (name != "@").then_some("@");
+    parents.chain(apex)
+}
+
+/// Wildcard name that can synthesize records below `encloser`:
+/// `*` under the apex, `*.{encloser}` otherwise
+pub fn wildcard_below(encloser: &str) -> String {
+    if encloser == "@" {
+        "*".to_string()
+    } else {
+        format!("*.{}", encloser)
+    }
+}
+
+fn check_label(label: &str) -> Result<(), &'static str> {
+    if label.is_empty() {
+        return Err("empty label");
+    }
+    if label.len() > MAX_LABEL_LEN {
+        return Err("label longer than 63 bytes");
+    }
+    let body = label.strip_prefix('_').unwrap_or(label);
+    if body.is_empty() || !body.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err("labels may only contain letters, digits and '-'");
+    }
+    if body.starts_with('-') || body.ends_with('-') {
+        return Err("labels cannot start or end with '-'");
+    }
+    // "??--" labels are reserved for IDNA, and only "xn--" is in use
+    if body.get(2..4) == Some("--") {
+        let encoded = body
+            .strip_prefix("xn--")
+            .ok_or("labels with '--' in the third and fourth positions are reserved")?;
+        match decode_punycode(encoded) {
+            Some(decoded) if !decoded.is_ascii() && !decoded.chars().any(char::is_control) => {}
+            _ => return Err("invalid punycode label"),
+        }
+    }
+    Ok(())
+}
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/// Decode a punycode string (RFC 3492), without the `xn--` prefix
+fn decode_punycode(input: &str) -> Option<String> {
+    let (basic, encoded) = match input.rfind('-') {
+        Some(pos) => (&input[..pos], &input[pos + 1..]),
+        None => ("", input),
+    };
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+
+    let mut digits = encoded.bytes().peekable();
+    while digits.peek().is_some() {
+        let old_i = i;
+        let mut weight: u32 = 1;
+        let mut k = BASE;
+        loop {
+            let digit = match digits.next()? {
+                c @ b'a'..=b'z' => (c - b'a') as u32,
+                c @ b'0'..=b'9' => (c - b'0') as u32 + 26,
+                _ => return None,
+            };
+            i = i.checked_add(digit.checked_mul(weight)?)?;
+            let t = if k <= bias {
+                T_MIN
+            } else if k >= bias + T_MAX {
+                T_MAX
+            } else {
+                k - bias
+            };
+            if digit < t {
+                break;
+            }
+            weight = weight.checked_mul(BASE - t)?;
+            k += BASE;
+        }
+        let len = output.len() as u32 + 1;
+        bias = adapt(i - old_i, len, old_i == 0);
+        n = n.checked_add(i / len)?;
+        i %= len;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    Some(output.into_iter().collect())
+}
+
+fn adapt(mut delta: u32, num_points: u32, first: bool) -> u32 {
+    delta /= if first { DAMP } else { 2 };
+    delta += delta / num_points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_names() {
+        assert_eq!(normalize_name("@").unwrap(), "@");
+        assert_eq!(normalize_name("WWW").unwrap(), "www");
+        assert_eq!(normalize_name("*").unwrap(), "*");
+        assert_eq!(normalize_name("*.api").unwrap(), "*.api");
+        assert_eq!(normalize_name("_sip._tcp").unwrap(), "_sip._tcp");
+        assert_eq!(normalize_name("xn--mnchen-3ya").unwrap(), "xn--mnchen-3ya");
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        for name in [
+            "",
+            "a:b",
+            "a b",
+            "a..b",
+            ".a",
+            "a.",
+            "-a",
+            "a-",
+            "api.*",
+            "a.@",
+            "ab--cd",
+            "xn--",
+            "xn--a",
+            "xn--zzzzzzzzzzzz",
+            "münchen",
+            &"a".repeat(64),
+            &["a"; 128].join("."),
+        ] {
+            assert!(
+                normalize_name(name).is_err(),
+                "{:?} should be rejected",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_ancestors() {
+        assert_eq!(
+            ancestors("a.b.c").collect::<Vec<_>>(),
+            vec!["b.c", "c", "@"]
+        );
+        assert_eq!(ancestors("www").collect::<Vec<_>>(), vec!["@"]);
+        assert_eq!(ancestors("@").count(), 0);
+        assert_eq!(wildcard_below("@"), "*");
+        assert_eq!(wildcard_below("api"), "*.api");
+    }
+
+    #[test]
+    fn test_decode_punycode() {
+        assert_eq!(decode_punycode("mnchen-3ya").unwrap(), "münchen");
+        assert_eq!(decode_punycode("bcher-kva").unwrap(), "bücher");
+        assert_eq!(decode_punycode("wgv71a119e").unwrap(), "日本語");
+    }
+}
@@ -0,0 +1,437 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::DnsRecord;
+
+/// Lowest TTL accepted, in seconds
+pub const MIN_TTL: u32 = 30;
+/// Highest TTL accepted, in seconds (one week)
+pub const MAX_TTL: u32 = 604_800;
+/// Longest TXT value accepted, in bytes (split into 255-byte strings when served)
+pub const MAX_TXT_LEN: usize = 2048;
+/// Preference/priority used for MX and SRV records without one
+pub const DEFAULT_PRIORITY: u16 = 10;
+
+/// Record types the contract accepts
+pub const RECORD_TYPES: [&str; 10] = [
+    "A", "AAAA", "CNAME", "MX", "NS", "TXT", "SRV", "SOA", "PTR", "CAA",
+];
+
+/// Parsed and validated record data
+///
+/// Records keep their `{record_type, value, ttl, priority}` JSON and storage
+/// shape; this is what the `value` (and `priority`) must parse into for the
+/// given type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    AAAA(Ipv6Addr),
+    CNAME(String),
+    NS(String),
+    PTR(String),
+    MX {
+        preference: u16,
+        exchange: String,
+    },
+    TXT(String),
+    /// Value format: "weight port target"
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    /// Value format: "mname rname serial refresh retry expire minimum"
+    SOA {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    /// Value format: "flags tag value"
+    CAA {
+        flags: u8,
+        tag: String,
+        value: String,
+    },
+}
+
+impl RecordData {
+    /// Parse a record value for the given type
+    pub fn parse(record_type: &str, value: &str, priority: Option<u16>) -> Result<Self, String> {
+        let record_type = record_type.to_uppercase();
+        let invalid = |expected: &str| {
+            format!(
+                "Invalid {} record value '{}': expected {}",
+                record_type, value, expected
+            )
+        };
+
+        let data = match record_type.as_str() {
+            "A" => RecordData::A(value.parse().map_err(|_| invalid("an IPv4 address"))?),
+            "AAAA" => RecordData::AAAA(value.parse().map_err(|_| invalid("an IPv6 address"))?),
+            "CNAME" => RecordData::CNAME(hostname(value).ok_or_else(|| invalid("a hostname"))?),
+            "NS" => RecordData::NS(hostname(value).ok_or_else(|| invalid("a hostname"))?),
+            "PTR" => RecordData::PTR(hostname(value).ok_or_else(|| invalid("a hostname"))?),
+            "MX" => RecordData::MX {
+                preference: priority.unwrap_or(DEFAULT_PRIORITY),
+                // "." is a null MX (RFC 7505)
+                exchange: root_or_hostname(value).ok_or_else(|| invalid("a hostname"))?,
+            },
+            "TXT" => {
+                if value.len() > MAX_TXT_LEN {
+                    return Err(format!(
+                        "Invalid TXT record value: {} bytes exceeds the {} byte limit",
+                        value.len(),
+                        MAX_TXT_LEN
+                    ));
+                }
+                RecordData::TXT(value.to_string())
+            }
+            "SRV" => {
+                let expected = "'weight port target'";
+                let [weight, port, target] = fields(value).ok_or_else(|| invalid(expected))?;
+                RecordData::SRV {
+                    priority: priority.unwrap_or(DEFAULT_PRIORITY),
+                    weight: weight.parse().map_err(|_| invalid(expected))?,
+                    port: port.parse().map_err(|_| invalid(expected))?,
+                    // "." means the service is not available (RFC 2782)
+                    target: root_or_hostname(target).ok_or_else(|| invalid(expected))?,
+                }
+            }
+            "SOA" => {
+                let expected = "'mname rname serial refresh retry expire minimum'";
+                let [mname, rname, serial, refresh, retry, expire, minimum] =
+                    fields(value).ok_or_else(|| invalid(expected))?;
+                // Refresh, retry and expire are signed 32-bit on the wire
+                let interval = |field: &str| {
+                    field
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|v| *v <= i32::MAX as u32)
+                        .ok_or_else(|| invalid(expected))
+                };
+                RecordData::SOA {
+                    mname: hostname(mname).ok_or_else(|| invalid(expected))?,
+                    rname: hostname(rname).ok_or_else(|| invalid(expected))?,
+                    serial: serial.parse().map_err(|_| invalid(expected))?,
+                    refresh: interval(refresh)?,
+                    retry: interval(retry)?,
+                    expire: interval(expire)?,
+                    minimum: minimum.parse().map_err(|_| invalid(expected))?,
+                }
+            }
+            "CAA" => {
+                let expected = "'flags tag value'";
+                let mut parts = value.splitn(3, char::is_whitespace);
+                let (Some(flags), Some(tag), Some(caa_value)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(invalid(expected));
+                };
+                if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return Err(invalid(expected));
+                }
+                RecordData::CAA {
+                    flags: flags.parse().map_err(|_| invalid(expected))?,
+                    tag: tag.to_lowercase(),
+                    value: caa_value.trim().trim_matches('"').to_string(),
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Unsupported record type '{}'; expected one of {}",
+                    record_type,
+                    RECORD_TYPES.join(", ")
+                ));
+            }
+        };
+        Ok(data)
+    }
+
+    /// The data with hostnames in canonical form: lower-case, without a
+    /// trailing dot
+    fn canonical(self) -> Self {
+        let bare = |name: String| match name.strip_suffix('.') {
+            Some(stripped) if !stripped.is_empty() => stripped.to_string(),
+            _ => name,
+        };
+        match self {
+            RecordData::CNAME(target) => RecordData::CNAME(bare(target)),
+            RecordData::NS(target) => RecordData::NS(bare(target)),
+            RecordData::PTR(target) => RecordData::PTR(bare(target)),
+            RecordData::MX {
+                preference,
+                exchange,
+            } => RecordData::MX {
+                preference,
+                exchange: bare(exchange),
+            },
+            RecordData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => RecordData::SRV {
+                priority,
+                weight,
+                port,
+                target: bare(target),
+            },
+            RecordData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => RecordData::SOA {
+                mname: bare(mname),
+                rname: bare(rname),
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            },
+            data => data,
+        }
+    }
+
+    /// The record type name, e.g. "A"
+    pub fn record_type(&self) -> &'static str {
+        match self {
+            RecordData::A(_) => "A",
+            RecordData::AAAA(_) => "AAAA",
+            RecordData::CNAME(_) => "CNAME",
+            RecordData::NS(_) => "NS",
+            RecordData::PTR(_) => "PTR",
+            RecordData::MX { .. } => "MX",
+            RecordData::TXT(_) => "TXT",
+            RecordData::SRV { .. } => "SRV",
+            RecordData::SOA { .. } => "SOA",
+            RecordData::CAA { .. } => "CAA",
+        }
+    }
+}
+
+impl DnsRecord {
+    /// Validate the record, returning its typed data
+    pub fn validate(&self) -> Result<RecordData, String> {
+        if !(MIN_TTL..=MAX_TTL).contains(&self.ttl) {
+            return Err(format!(
+                "Invalid TTL {} for {} record: must be between {} and {} seconds",
+                self.ttl,
+                self.record_type.to_uppercase(),
+                MIN_TTL,
+                MAX_TTL
+            ));
+        }
+        let backwards = matches!(
+            (self.valid_from, self.valid_until),
+            (Some(from), Some(until)) if from.0 >= until.0
+        );
+        if backwards {
+            return Err(format!(
+                "Invalid validity window for {} record: valid_from must be before valid_until",
+                self.record_type.to_uppercase()
+            ));
+        }
+        RecordData::parse(&self.record_type, &self.value, self.priority)
+    }
+
+    /// Whether two records have the same type and value, whatever their TTL,
+    /// priority or validity
+    ///
+    /// Values are compared as parsed, so `2001:db8::1` and `2001:DB8:0::1`
+    /// match, as do hostnames differing only in case or a trailing dot.
+    /// Values that don't parse are compared as written.
+    pub fn same_data(&self, other: &DnsRecord) -> bool {
+        if !self.record_type.eq_ignore_ascii_case(&other.record_type) {
+            return false;
+        }
+        match (self.canonical_data(), other.canonical_data()) {
+            (Some(data), Some(other_data)) => data == other_data,
+            _ => self.value == other.value,
+        }
+    }
+
+    /// The parsed value in canonical form, leaving out the priority
+    fn canonical_data(&self) -> Option<RecordData> {
+        RecordData::parse(&self.record_type, &self.value, None)
+            .ok()
+            .map(RecordData::canonical)
+    }
+
+    /// Whether the record is served at block timestamp `now`
+    pub fn is_active(&self, now: u64) -> bool {
+        self.valid_from.is_none_or(|from| now >= from.0) && !self.is_expired(now)
+    }
+
+    /// Whether the record's `valid_until` has passed at block timestamp `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.valid_until.is_some_and(|until| now >= until.0)
+    }
+}
+
+/// Split a value into exactly `N` whitespace-separated fields
+fn fields<const N: usize>(value: &str) -> Option<[&str; N]> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    parts.try_into().ok()
+}
+
+/// Lowercase a hostname, or `None` if it isn't one
+///
+/// Accepts `@` (the zone itself), or dot-separated labels of letters, digits,
+/// `-` and `_`, absolute (trailing dot) or relative to the zone.
+pub fn hostname(name: &str) -> Option<String> {
+    if name == "@" {
+        return Some(name.to_string());
+    }
+    let trimmed = name.strip_suffix('.').unwrap_or(name);
+    if trimmed.is_empty() || trimmed.len() > 253 {
+        return None;
+    }
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    };
+    trimmed
+        .split('.')
+        .all(valid_label)
+        .then(|| name.to_lowercase())
+}
+
+fn root_or_hostname(name: &str) -> Option<String> {
+    if name == "." {
+        Some(name.to_string())
+    } else {
+        hostname(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::json_types::U64;
+
+    fn record(record_type: &str, value: &str) -> DnsRecord {
+        DnsRecord {
+            record_type: record_type.to_string(),
+            value: value.to_string(),
+            ttl: 300,
+            priority: None,
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_valid_records() {
+        assert_eq!(
+            record("a", "192.168.1.1").validate(),
+            Ok(RecordData::A(Ipv4Addr::new(192, 168, 1, 1)))
+        );
+        assert!(record("AAAA", "2001:db8::1").validate().is_ok());
+        assert!(record("CNAME", "www.example.com.").validate().is_ok());
+        assert!(record("MX", "mail").validate().is_ok());
+        assert!(record("SRV", "5 5060 sip.example.com.").validate().is_ok());
+        assert!(
+            record("SOA", "ns1 hostmaster 1 7200 3600 1209600 300")
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            record("CAA", "0 issue \"letsencrypt.org\"")
+                .validate()
+                .is_ok()
+        );
+        assert!(record("TXT", "v=spf1 -all").validate().is_ok());
+    }
+
+    #[test]
+    fn test_rejects_invalid_values() {
+        assert!(
+            record("A", "not-an-ip")
+                .validate()
+                .unwrap_err()
+                .contains("IPv4")
+        );
+        assert!(record("AAAA", "1.2.3.4").validate().is_err());
+        assert!(record("CNAME", "bad host!").validate().is_err());
+        assert!(record("CNAME", "-leading.example").validate().is_err());
+        assert!(record("SRV", "5 5060").validate().is_err());
+        assert!(record("SRV", "5 99999 sip").validate().is_err());
+        assert!(
+            record("SOA", "ns1 hostmaster 1 7200 3600 4294967295 300")
+                .validate()
+                .is_err()
+        );
+        assert!(
+            record("TXT", &"x".repeat(MAX_TXT_LEN + 1))
+                .validate()
+                .is_err()
+        );
+        assert!(
+            record("HINFO", "x86 linux")
+                .validate()
+                .unwrap_err()
+                .contains("Unsupported")
+        );
+    }
+
+    #[test]
+    fn test_ttl_bounds() {
+        let mut r = record("A", "1.2.3.4");
+        r.ttl = 0;
+        assert!(r.validate().unwrap_err().contains("TTL"));
+        r.ttl = MAX_TTL + 1;
+        assert!(r.validate().is_err());
+        r.ttl = MIN_TTL;
+        assert!(r.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validity_window() {
+        let mut r = record("TXT", "acme-token");
+        assert!(r.is_active(0));
+
+        r.valid_from = Some(U64(100));
+        r.valid_until = Some(U64(200));
+        assert!(r.validate().is_ok());
+        assert!(!r.is_active(99));
+        assert!(r.is_active(100));
+        assert!(!r.is_active(200));
+        assert!(!r.is_expired(199));
+        assert!(r.is_expired(200));
+
+        r.valid_from = Some(U64(200));
+        assert!(r.validate().unwrap_err().contains("valid_from"));
+    }
+
+    #[test]
+    fn test_same_data() {
+        let mut other = record("a", "1.2.3.4");
+        other.ttl = 60;
+        assert!(record("A", "1.2.3.4").same_data(&other));
+        assert!(!record("A", "1.2.3.5").same_data(&other));
+        assert!(!record("TXT", "1.2.3.4").same_data(&other));
+
+        // Values are compared as parsed, hostnames in canonical form
+        assert!(record("AAAA", "2001:db8::1").same_data(&record("AAAA", "2001:DB8:0::1")));
+        let cname = record("CNAME", "Target.Example.com.");
+        assert!(cname.same_data(&record("CNAME", "target.example.com")));
+        let srv = record("SRV", "5 443 sip.example.com.");
+        assert!(srv.same_data(&record("SRV", "5 443 SIP.example.com")));
+        assert!(!record("MX", ".").same_data(&record("MX", "mail.example.com")));
+        assert!(!record("TXT", "Token").same_data(&record("TXT", "token")));
+    }
+}
@@ -0,0 +1,116 @@
+use near_sdk::borsh::{self, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::{AccountId, NearToken, Promise, env, near};
+
+/// Extra bytes the protocol charges for every storage entry
+const ENTRY_OVERHEAD: u64 = 40;
+
+/// Storage the contract currently uses
+#[near(serializers = [json])]
+#[derive(Clone, Debug)]
+pub struct StorageUsage {
+    /// Bytes used by the contract account, including its code
+    pub bytes: U64,
+    /// Balance locked to pay for those bytes
+    pub locked: NearToken,
+}
+
+/// Storage effect of a change, reported before it is made
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageCost {
+    /// Change in storage, in bytes; negative when storage is released
+    pub bytes: i64,
+    /// Deposit to attach to the call (zero when storage is released)
+    pub deposit: NearToken,
+}
+
+impl StorageCost {
+    pub fn from_bytes(bytes: i64) -> Self {
+        let deposit = if bytes > 0 {
+            env::storage_byte_cost().saturating_mul(bytes as u128)
+        } else {
+            NearToken::from_yoctonear(0)
+        };
+        Self { bytes, deposit }
+    }
+}
+
+pub fn current_usage() -> StorageUsage {
+    let bytes = env::storage_usage();
+    StorageUsage {
+        bytes: bytes.into(),
+        locked: env::storage_byte_cost().saturating_mul(bytes as u128),
+    }
+}
+
+/// Bytes used by one entry of an `IterableMap` with a one-byte prefix
+///
+/// Each entry is stored twice: the key in the map's key list
+/// (`prefix 'v' index -> key`) and the value under the SHA-256 of
+/// `prefix 'm' key` (`hash -> value index`).
+pub fn map_entry_bytes(key_len: usize, value_len: usize) -> u64 {
+    let (key_len, value_len) = (key_len as u64, value_len as u64);
+    let key_slot = 2 + 4 + key_len + ENTRY_OVERHEAD;
+    let value_slot = 32 + value_len + 4 + ENTRY_OVERHEAD;
+    key_slot + value_slot
+}
+
+/// Bytes used by one entry of a `LookupMap` with a one-byte prefix, which
+/// stores the value under `prefix key`
+pub fn lookup_entry_bytes(key_len: usize, value_len: usize) -> u64 {
+    1 + key_len as u64 + value_len as u64 + ENTRY_OVERHEAD
+}
+
+/// Length of a value's Borsh encoding, as stored
+pub fn serialized_len<T: BorshSerialize>(value: &T) -> usize {
+    borsh::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
+/// Charge or refund the storage change since `initial_usage`
+///
+/// Growth must be covered by the attached deposit; anything attached beyond
+/// that goes back to the caller. When storage shrinks, the attached deposit
+/// goes back to the caller and the released balance to `owner`.
+///
+/// Collections must be flushed before calling this so their writes count.
+pub fn settle(initial_usage: u64, owner: &AccountId) {
+    let attached = env::attached_deposit();
+    let caller = env::predecessor_account_id();
+    let byte_cost = env::storage_byte_cost();
+    let current_usage = env::storage_usage();
+
+    if current_usage >= initial_usage {
+        let bytes = current_usage - initial_usage;
+        let required = byte_cost.saturating_mul(bytes as u128);
+        assert!(
+            attached >= required,
+            "Attach at least {} to cover {} bytes of storage",
+            required.exact_amount_display(),
+            bytes
+        );
+        refund(&caller, attached.saturating_sub(required));
+    } else {
+        let bytes = initial_usage - current_usage;
+        refund(&caller, attached);
+        refund(owner, byte_cost.saturating_mul(bytes as u128));
+    }
+}
+
+fn refund(account: &AccountId, amount: NearToken) {
+    if !amount.is_zero() {
+        Promise::new(account.clone()).transfer(amount).detach();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_entry_bytes() {
+        // Two entries of overhead, the key and the hash of the key
+        assert_eq!(map_entry_bytes(10, 0), 2 * ENTRY_OVERHEAD + 10 + 32 + 10);
+        assert_eq!(map_entry_bytes(10, 50) - map_entry_bytes(10, 0), 50);
+    }
+}
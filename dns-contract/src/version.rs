@@ -0,0 +1,48 @@
+use near_sdk::near;
+
+/// Version of the state layout this code reads and writes
+///
+/// Bump it with every change to the stored layout of `DnsContract` or of a
+/// type stored in it, and teach `migration::migrate_state` to convert the
+/// previous layout.
+pub const STATE_VERSION: u32 = 1;
+
+/// Features this code supports, as reported by `dns_capabilities`
+///
+/// Names are only ever added, so clients can test for the ones they need:
+/// * `resolve`: `dns_resolve` and `dns_resolve_many`
+/// * `batch`: `dns_query_many` and `dns_resolve_many`
+/// * `apply`: atomic `dns_apply` with `expected_serial`
+/// * `zone_serial`: `dns_zone_serial` and `dns_zone_info`
+/// * `paging`: `dns_list_names_page`, `dns_list_records_page` and counts
+/// * `history`: change sets and point-in-time views
+/// * `validity`: records with `valid_from` and `valid_until`
+/// * `record_edit`: `dns_remove_record` and `dns_replace_record`
+/// * `lint`: coexistence rules and `dns_lint`
+/// * `roles`: admins and editor grants
+/// * `events`: NEP-297 change events
+/// * `upgrade`: `upgrade` and versioned state
+pub const CAPABILITIES: [&str; 12] = [
+    "resolve",
+    "batch",
+    "apply",
+    "zone_serial",
+    "paging",
+    "history",
+    "validity",
+    "record_edit",
+    "lint",
+    "roles",
+    "events",
+    "upgrade",
+];
+
+/// Version of the deployed code and of the state it keeps
+#[near(serializers = [json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionInfo {
+    /// Crate version of the contract code, e.g. `0.1.0`
+    pub version: String,
+    /// Layout version of the stored state
+    pub state_version: u32,
+}
@@ -0,0 +1,414 @@
+use near_sdk::json_types::{Base58CryptoHash, U64};
+use near_sdk::store::IterableMap;
+use near_sdk::{AccountId, Gas, NearToken, PanicOnDefault, Promise, PromiseError, env, near};
+
+/// Least deposit accepted by `create_zone`, which funds the zone account
+///
+/// The zone uses the DNS code as a global contract, so it only pays for its
+/// own state; this covers the account and a few hundred records.
+pub const MIN_ZONE_DEPOSIT: NearToken = NearToken::from_millinear(100);
+/// Deposit the helper attaches to `register_zone` for the registry entry
+///
+/// Taken from the deposit given to `create_zone`; what the entry doesn't
+/// use is refunded to the caller.
+pub const REGISTER_DEPOSIT: NearToken = NearToken::from_millinear(10);
+/// Most zones returned by one `list_zones` call
+pub const MAX_LIST_LIMIT: u32 = 100;
+
+const LOOKUP_GAS: Gas = Gas::from_tgas(5);
+const CREATE_GAS: Gas = Gas::from_tgas(80);
+const INIT_GAS: Gas = Gas::from_tgas(20);
+const CONFIRM_GAS: Gas = Gas::from_tgas(40);
+const REGISTER_GAS: Gas = Gas::from_tgas(30);
+const OWNER_GAS: Gas = Gas::from_tgas(5);
+const RECORD_GAS: Gas = Gas::from_tgas(10);
+
+/// A zone created through the factory
+#[near(serializers = [borsh, json])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZoneEntry {
+    /// Zone contract account, e.g. `dns.alice.near`
+    pub zone: AccountId,
+    /// Account that created the zone and owns it
+    pub owner: AccountId,
+    /// Hash of the DNS code the factory published when the zone registered,
+    /// which the helper creates zones with
+    pub code_hash: Base58CryptoHash,
+    /// Block timestamp of the creation, in nanoseconds
+    pub created_at: U64,
+}
+
+/// Creates `dns.<account>` zones and keeps a registry of them
+///
+/// NEAR only lets an account create its own subaccounts, so the factory
+/// can't create `dns.alice.near` itself. The same code runs in two places:
+///
+/// * at the factory account (e.g. `dns-factory.near`), initialized with
+///   `new`, it holds the DNS code hash and the registry
+/// * at the caller's account, as a stateless helper: `create_zone` there
+///   asks the factory for the code hash, creates and funds `dns.<caller>`,
+///   deploys the DNS contract to it as a global contract and initializes it,
+///   which makes the caller its owner, then registers it with the factory
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct DnsFactory {
+    /// Account that can change the DNS code hash
+    owner: AccountId,
+    /// Hash of the DNS contract code, published as a global contract
+    dns_code_hash: Base58CryptoHash,
+    /// Zones created through the factory, by zone account
+    zones: IterableMap<AccountId, ZoneEntry>,
+}
+
+#[near]
+impl DnsFactory {
+    /// Initialize the factory with the hash of the published DNS contract
+    #[init]
+    pub fn new(dns_code_hash: Base58CryptoHash) -> Self {
+        Self {
+            owner: env::predecessor_account_id(),
+            dns_code_hash,
+            zones: IterableMap::new(b"z"),
+        }
+    }
+
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner,
+            "Only the owner ({}) can call this method",
+            self.owner
+        );
+    }
+
+    /// The zone account of `account`
+    fn zone_of(account: &AccountId) -> AccountId {
+        format!("dns.{}", account).parse().unwrap_or_else(|_| {
+            env::panic_str(&format!("'dns.{}' is not a valid account", account))
+        })
+    }
+
+    // ========== HELPER METHODS (run at the caller's account) ==========
+
+    /// Create `dns.<this account>` with the DNS code published by `factory`
+    ///
+    /// Runs at the caller's own account, where the factory code is deployed
+    /// as a helper, and only the account itself can call it. The attached
+    /// deposit (at least 0.11 NEAR) funds the zone, less the 0.01 NEAR
+    /// registration deposit. It takes no state, so the SDK doesn't reject
+    /// deposits and it needs no `#[payable]`.
+    #[private]
+    pub fn create_zone(factory: AccountId) -> Promise {
+        let required = MIN_ZONE_DEPOSIT.saturating_add(REGISTER_DEPOSIT);
+        let attached = env::attached_deposit();
+        assert!(
+            attached >= required,
+            "Attach at least {} to fund the zone",
+            required.exact_amount_display()
+        );
+        let amount = attached.saturating_sub(REGISTER_DEPOSIT);
+        Self::ext(factory.clone())
+            .with_static_gas(LOOKUP_GAS)
+            .get_dns_code_hash()
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(CREATE_GAS)
+                    .on_dns_code_hash(factory, amount),
+            )
+    }
+
+    /// Create, fund and initialize the zone with the code hash from the factory
+    #[private]
+    pub fn on_dns_code_hash(
+        factory: AccountId,
+        amount: NearToken,
+        #[callback_result] code_hash: Result<Base58CryptoHash, PromiseError>,
+    ) -> Promise {
+        let code_hash = code_hash.unwrap_or_else(|_| {
+            env::panic_str(&format!("Could not get the DNS code hash from {}", factory))
+        });
+        let zone = Self::zone_of(&env::current_account_id());
+        env::log_str(&format!(
+            "Creating {} with code {}",
+            zone,
+            String::from(&code_hash)
+        ));
+
+        // A failed creation returns the funds to this account
+        Promise::new(zone.clone())
+            .create_account()
+            .transfer(amount)
+            .use_global_contract(code_hash)
+            .function_call(
+                "new".to_string(),
+                Vec::new(),
+                NearToken::from_yoctonear(0),
+                INIT_GAS,
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(CONFIRM_GAS)
+                    .on_zone_created(factory, zone),
+            )
+    }
+
+    /// Register the zone with the factory once it is initialized
+    #[private]
+    pub fn on_zone_created(factory: AccountId, zone: AccountId) -> Promise {
+        // `new` returns nothing, so a successful result is empty
+        if env::promise_result_checked(0, 0).is_err() {
+            env::panic_str(&format!("Creating {} failed", zone));
+        }
+        Self::ext(factory)
+            .with_static_gas(REGISTER_GAS)
+            .with_attached_deposit(REGISTER_DEPOSIT)
+            .register_zone()
+    }
+
+    // ========== FACTORY METHODS ==========
+
+    /// Record the caller's zone, created by the helper at the caller's account
+    ///
+    /// Only `dns.<caller>` can be registered, so an account can only list
+    /// its own zone, and only once the zone confirms it: the factory asks it
+    /// for its owner, which must be the caller, before writing the entry.
+    /// The caller pays for the entry's storage with the attached deposit
+    /// (at least 0.01 NEAR); what it doesn't use, or all of it if the zone
+    /// isn't confirmed, is refunded.
+    #[payable]
+    pub fn register_zone(&mut self) -> Promise {
+        let attached = env::attached_deposit();
+        assert!(
+            attached >= REGISTER_DEPOSIT,
+            "Attach at least {} to cover the registry entry",
+            REGISTER_DEPOSIT.exact_amount_display()
+        );
+        let owner = env::predecessor_account_id();
+        let zone = Self::zone_of(&owner);
+        Promise::new(zone)
+            .function_call(
+                "get_owner".to_string(),
+                Vec::new(),
+                NearToken::from_yoctonear(0),
+                OWNER_GAS,
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(RECORD_GAS)
+                    .on_zone_confirmed(owner, attached),
+            )
+    }
+
+    /// Write the registry entry if the zone reported the caller as its owner
+    ///
+    /// Returns whether the zone was registered; the deposit is refunded when
+    /// it wasn't.
+    #[private]
+    pub fn on_zone_confirmed(
+        &mut self,
+        owner: AccountId,
+        deposit: NearToken,
+        #[callback_result] zone_owner: Result<AccountId, PromiseError>,
+    ) -> bool {
+        let zone = Self::zone_of(&owner);
+        if zone_owner.as_ref() != Ok(&owner) {
+            env::log_str(&format!("{} is not a DNS zone owned by {}", zone, owner));
+            Self::refund(&owner, deposit);
+            return false;
+        }
+
+        let initial_usage = env::storage_usage();
+        let entry = ZoneEntry {
+            zone: zone.clone(),
+            owner: owner.clone(),
+            code_hash: self.dns_code_hash,
+            created_at: U64(env::block_timestamp()),
+        };
+        let replaced = self.zones.insert(zone.clone(), entry);
+        self.zones.flush();
+
+        let bytes = env::storage_usage().saturating_sub(initial_usage);
+        let required = env::storage_byte_cost().saturating_mul(bytes as u128);
+        if deposit < required {
+            env::log_str(&format!(
+                "Registering {} needs {} for {} bytes of storage",
+                zone,
+                required.exact_amount_display(),
+                bytes
+            ));
+            match replaced {
+                Some(previous) => self.zones.insert(zone, previous),
+                None => self.zones.remove(&zone),
+            };
+            Self::refund(&owner, deposit);
+            return false;
+        }
+        Self::refund(&owner, deposit.saturating_sub(required));
+        env::log_str(&format!("Registered zone {}", zone));
+        true
+    }
+
+    fn refund(account: &AccountId, amount: NearToken) {
+        if !amount.is_zero() {
+            Promise::new(account.clone()).transfer(amount).detach();
+        }
+    }
+
+    /// Publish a new DNS contract build for zones created from now on (owner
+    /// only)
+    ///
+    /// Zones that exist keep their code until their owners upgrade them.
+    pub fn set_dns_code_hash(&mut self, dns_code_hash: Base58CryptoHash) {
+        self.assert_owner();
+        self.dns_code_hash = dns_code_hash;
+    }
+
+    // ========== VIEW METHODS ==========
+
+    /// Hash of the DNS contract code new zones get
+    pub fn get_dns_code_hash(&self) -> Base58CryptoHash {
+        self.dns_code_hash
+    }
+
+    /// The zone registered for `owner`, if any
+    pub fn get_zone(&self, owner: AccountId) -> Option<ZoneEntry> {
+        self.zones.get(&Self::zone_of(&owner)).cloned()
+    }
+
+    /// Zones created through the factory, in registration order
+    ///
+    /// Returns at most `limit` zones (default and maximum 100) starting at
+    /// `from_index`.
+    pub fn list_zones(&self, from_index: Option<u32>, limit: Option<u32>) -> Vec<ZoneEntry> {
+        let limit = limit.unwrap_or(MAX_LIST_LIMIT).min(MAX_LIST_LIMIT);
+        self.zones
+            .values()
+            .skip(from_index.unwrap_or(0) as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    /// Number of zones created through the factory
+    pub fn count_zones(&self) -> u32 {
+        self.zones.len()
+    }
+
+    /// Account that can change the DNS code hash
+    pub fn get_owner(&self) -> AccountId {
+        self.owner.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn get_context(predecessor: &str) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder.predecessor_account_id(predecessor.parse().unwrap());
+        builder.current_account_id("dns-factory.testnet".parse().unwrap());
+        builder
+    }
+
+    fn code_hash(byte: u8) -> Base58CryptoHash {
+        Base58CryptoHash::from([byte; 32])
+    }
+
+    /// Run the registration callback at the factory as the zone's reply
+    fn confirm(
+        factory: &mut DnsFactory,
+        owner: &str,
+        deposit: NearToken,
+        zone_owner: Result<AccountId, PromiseError>,
+    ) -> bool {
+        testing_env!(get_context("dns-factory.testnet").build());
+        factory.on_zone_confirmed(owner.parse().unwrap(), deposit, zone_owner)
+    }
+
+    #[test]
+    fn test_register_and_list_zones() {
+        testing_env!(get_context("factory-owner.testnet").build());
+        let mut factory = DnsFactory::new(code_hash(1));
+
+        for account in ["alice.testnet", "bob.testnet"] {
+            let owner = Ok(account.parse().unwrap());
+            assert!(confirm(&mut factory, account, REGISTER_DEPOSIT, owner));
+        }
+
+        assert_eq!(factory.count_zones(), 2);
+        let alice = factory.get_zone("alice.testnet".parse().unwrap()).unwrap();
+        assert_eq!(alice.zone.as_str(), "dns.alice.testnet");
+        assert_eq!(alice.owner.as_str(), "alice.testnet");
+        assert_eq!(alice.code_hash, code_hash(1));
+        assert!(factory.get_zone("carol.testnet".parse().unwrap()).is_none());
+
+        let page = factory.list_zones(Some(1), Some(10));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].zone.as_str(), "dns.bob.testnet");
+    }
+
+    #[test]
+    fn test_unconfirmed_zones_are_not_registered() {
+        testing_env!(get_context("factory-owner.testnet").build());
+        let mut factory = DnsFactory::new(code_hash(1));
+
+        // No zone contract, a zone owned by someone else, and too little
+        // deposit for the entry
+        assert!(!confirm(
+            &mut factory,
+            "alice.testnet",
+            REGISTER_DEPOSIT,
+            Err(PromiseError::Failed)
+        ));
+        let mallory = Ok("mallory.testnet".parse().unwrap());
+        assert!(!confirm(
+            &mut factory,
+            "alice.testnet",
+            REGISTER_DEPOSIT,
+            mallory
+        ));
+        let alice = Ok("alice.testnet".parse().unwrap());
+        assert!(!confirm(
+            &mut factory,
+            "alice.testnet",
+            NearToken::from_yoctonear(1),
+            alice
+        ));
+        assert_eq!(factory.count_zones(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "to cover")]
+    fn test_register_zone_requires_storage_deposit() {
+        testing_env!(get_context("factory-owner.testnet").build());
+        let mut factory = DnsFactory::new(code_hash(1));
+
+        testing_env!(get_context("alice.testnet").build());
+        factory.register_zone().detach();
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner (factory-owner.testnet) can call this method")]
+    fn test_set_dns_code_hash_owner_only() {
+        testing_env!(get_context("factory-owner.testnet").build());
+        let mut factory = DnsFactory::new(code_hash(1));
+        factory.set_dns_code_hash(code_hash(2));
+        assert_eq!(factory.get_dns_code_hash(), code_hash(2));
+
+        testing_env!(get_context("alice.testnet").build());
+        factory.set_dns_code_hash(code_hash(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "Attach at least")]
+    fn test_create_zone_requires_deposit() {
+        let mut context = get_context("alice.testnet");
+        context.current_account_id("alice.testnet".parse().unwrap());
+        context.attached_deposit(NearToken::from_millinear(10));
+        testing_env!(context.build());
+        DnsFactory::create_zone("dns-factory.testnet".parse().unwrap()).detach();
+    }
+}
@@ -0,0 +1,131 @@
+//! Simple UDP load generator for the NEAR DNS server
+//!
+//! Fires queries from many concurrent clients for a fixed duration and reports
+//! the achieved throughput. Run it against servers started with different
+//! `--udp-sockets` values to compare how UDP handling scales across cores:
+//!
+//! ```bash
+//! near-dns-server --bind 127.0.0.1:5355/udp --udp-sockets 1
+//! cargo run --release --example udp_load -- --server 127.0.0.1:5355
+//!
+//! near-dns-server --bind 127.0.0.1:5355/udp --udp-sockets 8
+//! cargo run --release --example udp_load -- --server 127.0.0.1:5355
+//! ```
+
+use clap::Parser;
+use hickory_proto::op::{Message, MessageType, OpCode, Query};
+use hickory_proto::rr::{Name, RecordType};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+#[derive(Parser, Debug)]
+#[command(about = "UDP load generator for near-dns-server")]
+struct Args {
+    /// Server address to query
+    #[arg(short, long, default_value = "127.0.0.1:5355")]
+    server: SocketAddr,
+
+    /// Number of concurrent clients (each with its own source port)
+    #[arg(short, long, default_value = "64")]
+    clients: usize,
+
+    /// Test duration in seconds
+    #[arg(short, long, default_value = "10")]
+    duration: u64,
+
+    /// Per-query timeout in milliseconds
+    #[arg(long, default_value = "2000")]
+    timeout_ms: u64,
+
+    /// Names to query, round-robin
+    #[arg(short, long, default_value = "neardns.near")]
+    name: Vec<String>,
+}
+
+#[derive(Default)]
+struct Stats {
+    sent: AtomicU64,
+    received: AtomicU64,
+    timeouts: AtomicU64,
+}
+
+fn build_query(id: u16, name: &Name) -> Vec<u8> {
+    let mut message = Message::new();
+    message
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name.clone(), RecordType::A));
+    message.to_vec().expect("query encodes")
+}
+
+async fn run_client(args: Arc<Args>, names: Arc<Vec<Name>>, stats: Arc<Stats>, deadline: Instant) {
+    let bind: SocketAddr = if args.server.is_ipv6() {
+        "[::]:0".parse().unwrap()
+    } else {
+        "0.0.0.0:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(bind).await.expect("bind client socket");
+    socket.connect(args.server).await.expect("connect client socket");
+
+    let timeout = Duration::from_millis(args.timeout_ms);
+    let mut buf = [0u8; 4096];
+    let mut id: u16 = 0;
+
+    while Instant::now() < deadline {
+        id = id.wrapping_add(1);
+        let query = build_query(id, &names[id as usize % names.len()]);
+        if socket.send(&query).await.is_err() {
+            continue;
+        }
+        stats.sent.fetch_add(1, Ordering::Relaxed);
+
+        match tokio::time::timeout(timeout, socket.recv(&mut buf)).await {
+            Ok(Ok(len)) if len >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == id => {
+                stats.received.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(_) => {}
+            Err(_) => {
+                stats.timeouts.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Arc::new(Args::parse());
+    let names: Vec<Name> = args
+        .name
+        .iter()
+        .map(|n| Name::from_str(n).expect("valid query name"))
+        .collect();
+    let names = Arc::new(names);
+    let stats = Arc::new(Stats::default());
+
+    println!(
+        "Querying {} with {} clients for {}s",
+        args.server, args.clients, args.duration
+    );
+
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration);
+    let clients: Vec<_> = (0..args.clients)
+        .map(|_| tokio::spawn(run_client(args.clone(), names.clone(), stats.clone(), deadline)))
+        .collect();
+    for client in clients {
+        let _ = client.await;
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    let received = stats.received.load(Ordering::Relaxed);
+    println!("sent:      {}", stats.sent.load(Ordering::Relaxed));
+    println!("received:  {}", received);
+    println!("timeouts:  {}", stats.timeouts.load(Ordering::Relaxed));
+    println!("qps:       {:.0}", received as f64 / elapsed);
+}
@@ -0,0 +1,123 @@
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::net::IpAddr;
+
+/// What a client is asking the server to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Answer a query for a name in a NEAR zone
+    Answer,
+    /// Forward a query for a non-NEAR name to the upstream resolvers
+    Recursion,
+    /// Request a zone transfer (AXFR or IXFR)
+    Transfer,
+}
+
+/// Client access lists, by source subnet
+///
+/// Queries from clients outside the relevant list are answered with REFUSED.
+/// The defaults answer NEAR zones for anyone but only recurse for loopback and
+/// private ranges, so a server bound to a public address isn't an open resolver.
+///
+/// ```toml
+/// [acl]
+/// answer = ["0.0.0.0/0", "::/0"]
+/// recursion = ["10.0.0.0/8", "192.168.0.0/16"]
+/// transfer = ["0.0.0.0/0", "::/0"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AclConfig {
+    /// Clients allowed to get answers from NEAR zones
+    pub answer: Vec<IpNet>,
+    /// Clients allowed to use upstream recursion
+    pub recursion: Vec<IpNet>,
+    /// Clients allowed to request zone transfers
+    pub transfer: Vec<IpNet>,
+}
+
+/// Loopback, RFC 1918, CGNAT, link-local and IPv6 ULA ranges
+const PRIVATE_RANGES: &[&str] = &[
+    "127.0.0.0/8",
+    "10.0.0.0/8",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "100.64.0.0/10",
+    "169.254.0.0/16",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+];
+
+const ANY: &[&str] = &["0.0.0.0/0", "::/0"];
+
+fn nets(ranges: &[&str]) -> Vec<IpNet> {
+    ranges
+        .iter()
+        .map(|range| range.parse().expect("built-in range is valid"))
+        .collect()
+}
+
+impl Default for AclConfig {
+    fn default() -> Self {
+        Self {
+            answer: nets(ANY),
+            recursion: nets(PRIVATE_RANGES),
+            transfer: nets(ANY),
+        }
+    }
+}
+
+impl AclConfig {
+    /// Check whether `client` has `permission`
+    pub fn allows(&self, permission: Permission, client: IpAddr) -> bool {
+        // IPv4 clients on a dual-stack socket show up as ::ffff:a.b.c.d
+        let client = client.to_canonical();
+        let list = match permission {
+            Permission::Answer => &self.answer,
+            Permission::Recursion => &self.recursion,
+            Permission::Transfer => &self.transfer,
+        };
+        list.iter().any(|net| net.contains(&client))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_acl_is_not_an_open_resolver() {
+        let acl = AclConfig::default();
+        let public: IpAddr = "203.0.113.7".parse().unwrap();
+        let private: IpAddr = "192.168.1.20".parse().unwrap();
+
+        assert!(acl.allows(Permission::Answer, public));
+        assert!(!acl.allows(Permission::Recursion, public));
+        assert!(acl.allows(Permission::Recursion, private));
+        assert!(acl.allows(Permission::Recursion, "::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_ipv4_mapped_clients() {
+        let acl = AclConfig::default();
+        assert!(acl.allows(Permission::Recursion, "::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!acl.allows(Permission::Recursion, "::ffff:8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_acl() {
+        let acl: AclConfig = toml::from_str(
+            r#"
+            recursion = ["198.51.100.0/24"]
+            transfer = []
+            "#,
+        )
+        .unwrap();
+
+        assert!(acl.allows(Permission::Answer, "203.0.113.7".parse().unwrap()));
+        assert!(acl.allows(Permission::Recursion, "198.51.100.9".parse().unwrap()));
+        assert!(!acl.allows(Permission::Recursion, "10.0.0.1".parse().unwrap()));
+        assert!(!acl.allows(Permission::Transfer, "127.0.0.1".parse().unwrap()));
+    }
+}
@@ -1,18 +1,25 @@
 use async_trait::async_trait;
 use hickory_proto::op::ResponseCode;
-use hickory_proto::rr::{LowerName, Name, Record, RecordType};
+use hickory_proto::rr::rdata::PTR;
+use hickory_proto::rr::{LowerName, Name, RData, Record, RecordType};
 use hickory_server::authority::{
     Authority, LookupControlFlow, LookupObject, LookupOptions, MessageRequest, UpdateResult,
     ZoneType,
 };
 use hickory_server::server::RequestInfo;
+use std::net::IpAddr;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 use tracing::{debug, error, info, warn};
 
+use crate::acl::{AclConfig, Permission};
+use crate::config::{Config, ConfigError};
 use crate::records::{record_type_to_string, DnsRecord};
 use crate::resolver::near::{NearResolver, ResolverError};
 use crate::resolver::upstream::{UpstreamError, UpstreamResolver};
+use crate::reverse::{self, ReverseConfig, ReverseZone};
+use crate::soa::{self, SoaConfig};
 
 /// A lookup result that can be returned from the authority
 pub struct BlockchainLookup {
@@ -45,22 +52,66 @@ impl LookupObject for BlockchainLookup {
 /// 1. Checking if the TLD is a NEAR account (dynamic detection)
 /// 2. If yes: resolve via NEAR blockchain contracts
 /// 3. If no: forward to upstream DNS servers
+///
+/// Client requests are checked against the ACLs first; anything not allowed
+/// gets REFUSED.
+///
+/// Each NEAR zone gets an SOA synthesized from its contract's zone serial,
+/// answered at the zone apex and sent with negative answers so resolvers can
+/// cache them.
+///
+/// Reverse names in the configured reverse zones are answered from NEAR too,
+/// ahead of the TLD check.
 pub struct BlockchainAuthority {
     origin: LowerName,
     near_resolver: Arc<NearResolver>,
     upstream_resolver: Arc<UpstreamResolver>,
+    acl: RwLock<AclConfig>,
+    soa: RwLock<SoaConfig>,
+    reverse: RwLock<ReverseConfig>,
 }
 
 impl BlockchainAuthority {
     /// Create a new blockchain authority
-    pub fn new(near_resolver: NearResolver, upstream_resolver: UpstreamResolver) -> Self {
+    pub fn new(
+        near_resolver: NearResolver,
+        upstream_resolver: UpstreamResolver,
+        acl: AclConfig,
+        soa: SoaConfig,
+        reverse: ReverseConfig,
+    ) -> Self {
         Self {
             origin: LowerName::from(Name::root()),
             near_resolver: Arc::new(near_resolver),
             upstream_resolver: Arc::new(upstream_resolver),
+            acl: RwLock::new(acl),
+            soa: RwLock::new(soa),
+            reverse: RwLock::new(reverse),
         }
     }
 
+    /// Apply a reloaded configuration
+    ///
+    /// TLDs and upstreams are swapped in place; caches keep their entries unless
+    /// the cache limits changed. A changed code hash allowlist drops the cached
+    /// contract checks.
+    pub fn reload(&self, config: &Config) -> Result<(), ConfigError> {
+        let upstreams = config.upstream_addrs()?;
+
+        self.near_resolver.set_tlds(&config.tlds);
+        self.near_resolver.set_verify(&config.verify);
+        self.upstream_resolver.set_nameservers(&upstreams);
+        if self.near_resolver.cache().reconfigure((&config.cache).into()) {
+            info!("Cache limits changed, caches were flushed");
+        }
+        *self.acl.write().expect("ACL lock poisoned") = config.acl.clone();
+        *self.soa.write().expect("SOA lock poisoned") = config.soa.clone();
+        *self.reverse.write().expect("reverse lock poisoned") = config.reverse.clone();
+
+        info!(tlds = ?config.tlds, upstreams = ?upstreams, "Applied configuration");
+        Ok(())
+    }
+
     /// Extract TLD from a domain name
     fn extract_tld(name: &LowerName) -> Option<String> {
         let name_str = name.to_string();
@@ -73,16 +124,57 @@ impl BlockchainAuthority {
         }
     }
 
+    /// Whether a name is answered from NEAR: it belongs to a NEAR TLD or a
+    /// reverse zone
+    fn is_near_name(&self, name: &LowerName) -> bool {
+        Self::extract_tld(name).is_some_and(|tld| self.near_resolver.is_near_tld(&tld))
+            || self.reverse_zone(&Name::from(name.clone())).is_some()
+    }
+
+    /// The reverse zone serving `name`, if any
+    fn reverse_zone(&self, name: &Name) -> Option<ReverseZone> {
+        self.reverse
+            .read()
+            .expect("reverse lock poisoned")
+            .zone_for(name)
+            .cloned()
+    }
+
+    /// Check whether the client behind `request` may have it answered
+    fn is_allowed(&self, request: &RequestInfo<'_>) -> bool {
+        let acl = self.acl.read().expect("ACL lock poisoned");
+        let client = request.src.ip();
+        let rtype = request.query.query_type();
+
+        let is_transfer = matches!(rtype, RecordType::AXFR | RecordType::IXFR);
+        if is_transfer && !acl.allows(Permission::Transfer, client) {
+            return false;
+        }
+
+        let permission = if self.is_near_name(request.query.name()) {
+            Permission::Answer
+        } else {
+            Permission::Recursion
+        };
+        acl.allows(permission, client)
+    }
+
     /// Convert contract DnsRecords to hickory Records
+    ///
+    /// TTLs are capped at the time left before a record expires.
     fn convert_records(
         records: Vec<DnsRecord>,
         name: &Name,
         origin: &Name,
     ) -> Vec<Record> {
+        let now = SystemTime::now();
         records
             .into_iter()
             .filter_map(|r| match r.to_dns_record(name, origin) {
-                Ok(record) => Some(record),
+                Ok(mut record) => {
+                    record.set_ttl(r.ttl_at(now));
+                    Some(record)
+                }
                 Err(e) => {
                     warn!(error = %e, "Failed to convert record");
                     None
@@ -91,6 +183,85 @@ impl BlockchainAuthority {
             .collect()
     }
 
+    /// The zone `domain` belongs to: its origin, and the contract serving it
+    /// unless it's a reverse zone synthesized from names alone
+    async fn find_zone(&self, domain: &str) -> Option<(Name, Option<String>)> {
+        if let Some(zone) = Name::from_str(domain).ok().and_then(|name| self.reverse_zone(&name)) {
+            return Some((zone.origin(), zone.contract));
+        }
+
+        let zone = match self.near_resolver.find_zone(domain).await {
+            Ok(Some(zone)) => zone,
+            Ok(None) => return None,
+            Err(e) => {
+                debug!(domain = %domain, error = %e, "No zone for SOA");
+                return None;
+            }
+        };
+        let origin = Name::from_str(&format!("{}.", zone.name)).ok()?;
+        Some((origin, Some(zone.contract_id)))
+    }
+
+    /// Synthesize the SOA of the zone `domain` belongs to
+    ///
+    /// Returns `None` when no zone contract exists or it can't be reached.
+    /// Reverse zones without a contract get serial 0.
+    async fn zone_soa(&self, domain: &str) -> Option<Record> {
+        let (origin, contract_id) = self.find_zone(domain).await?;
+        let (serial, stored) = match &contract_id {
+            Some(contract_id) => {
+                let serial = match self.near_resolver.zone_serial(contract_id).await {
+                    Ok(serial) => serial,
+                    Err(e) => {
+                        warn!(contract_id = %contract_id, error = %e, "Failed to fetch zone serial");
+                        return None;
+                    }
+                };
+                let stored = self
+                    .near_resolver
+                    .query_contract(contract_id, "@", "SOA")
+                    .await
+                    .ok()
+                    .flatten()
+                    .and_then(|records| records.into_iter().next())
+                    .and_then(|record| record.to_rdata(&origin).ok());
+                (serial, stored)
+            }
+            None => (0, None),
+        };
+        let stored = match &stored {
+            Some(RData::SOA(soa)) => Some(soa),
+            _ => None,
+        };
+
+        let config = self.soa.read().expect("SOA lock poisoned").clone();
+        match config.synthesize(&origin, serial, stored) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                warn!(zone = %origin, error = %e, "Failed to synthesize SOA");
+                None
+            }
+        }
+    }
+
+    /// Negative answer for a NEAR name, with its zone's SOA in the authority
+    /// section: NODATA if the name exists with other types, NXDOMAIN otherwise
+    async fn near_negative(
+        &self,
+        domain: &str,
+        name_exists: bool,
+    ) -> LookupControlFlow<BlockchainLookup> {
+        if let Some(soa) = self.zone_soa(domain).await {
+            soa::set_negative_soa(soa);
+        }
+        let error = if name_exists {
+            hickory_server::authority::LookupError::for_name_exists()
+        } else {
+            hickory_server::authority::LookupError::from(ResponseCode::NXDomain)
+        };
+        LookupControlFlow::Break(Err(error))
+    }
+
     /// Handle NEAR domain resolution
     async fn resolve_near(
         &self,
@@ -102,6 +273,15 @@ impl BlockchainAuthority {
 
         debug!(domain = %domain, record_type = %record_type_str, "Resolving NEAR domain");
 
+        // The apex SOA always carries the contract's current serial
+        if rtype == RecordType::SOA {
+            let apex = Name::from(name.clone());
+            if let Some(soa) = self.zone_soa(&domain).await.filter(|soa| *soa.name() == apex) {
+                debug!(domain = %domain, "Answering with synthesized SOA");
+                return LookupControlFlow::Break(Ok(BlockchainLookup::new(vec![soa])));
+            }
+        }
+
         match self.near_resolver.resolve(&domain, &record_type_str).await {
             Ok(records) => {
                 let origin = Name::from_str(&domain).unwrap_or_else(|_| Name::root());
@@ -110,9 +290,7 @@ impl BlockchainAuthority {
 
                 if dns_records.is_empty() {
                     debug!(domain = %domain, "No valid records after conversion");
-                    LookupControlFlow::Break(Err(hickory_server::authority::LookupError::from(
-                        ResponseCode::NXDomain,
-                    )))
+                    self.near_negative(&domain, false).await
                 } else {
                     info!(domain = %domain, count = dns_records.len(), "Resolved NEAR domain");
                     LookupControlFlow::Break(Ok(BlockchainLookup::new(dns_records)))
@@ -120,9 +298,11 @@ impl BlockchainAuthority {
             }
             Err(ResolverError::NotFound) => {
                 debug!(domain = %domain, "NEAR domain not found (NXDOMAIN)");
-                LookupControlFlow::Break(Err(hickory_server::authority::LookupError::from(
-                    ResponseCode::NXDomain,
-                )))
+                self.near_negative(&domain, false).await
+            }
+            Err(ResolverError::NoData) => {
+                debug!(domain = %domain, "NEAR domain has no records of this type (NODATA)");
+                self.near_negative(&domain, true).await
             }
             Err(ResolverError::NotNearTld) => {
                 // This shouldn't happen if we checked is_near_tld first
@@ -138,6 +318,96 @@ impl BlockchainAuthority {
         }
     }
 
+    /// Handle a lookup in a reverse zone served from NEAR
+    ///
+    /// Records come from the zone's contract. A PTR query the contract has no
+    /// answer for gets a PTR synthesized from the zone's names.
+    async fn resolve_reverse(
+        &self,
+        name: &LowerName,
+        rtype: RecordType,
+        zone: ReverseZone,
+    ) -> LookupControlFlow<BlockchainLookup> {
+        let domain = name.to_string();
+        let query = Name::from(name.clone());
+        let origin = zone.origin();
+
+        if rtype == RecordType::SOA && query == origin {
+            if let Some(soa) = self.zone_soa(&domain).await {
+                debug!(domain = %domain, "Answering with synthesized SOA");
+                return LookupControlFlow::Break(Ok(BlockchainLookup::new(vec![soa])));
+            }
+        }
+
+        let mut name_exists = false;
+        if let Some(contract_id) = &zone.contract {
+            let dns_name = zone.relative_name(&query);
+            let record_type_str = record_type_to_string(rtype);
+            match self
+                .near_resolver
+                .resolve_in_zone(contract_id, &dns_name, &record_type_str)
+                .await
+            {
+                Ok(records) => {
+                    let dns_records = Self::convert_records(records, &query, &origin);
+                    if !dns_records.is_empty() {
+                        info!(domain = %domain, count = dns_records.len(), "Resolved reverse name");
+                        return LookupControlFlow::Break(Ok(BlockchainLookup::new(dns_records)));
+                    }
+                }
+                Err(ResolverError::NoData) => name_exists = true,
+                Err(ResolverError::NotFound) => {}
+                Err(e) => {
+                    error!(domain = %domain, error = %e, "Reverse resolution failed");
+                    return LookupControlFlow::Break(Err(
+                        hickory_server::authority::LookupError::from(ResponseCode::ServFail),
+                    ));
+                }
+            }
+        }
+
+        if rtype == RecordType::PTR {
+            if let Some(ptr) = self.synthesize_ptr(&query, &zone).await {
+                return LookupControlFlow::Break(Ok(BlockchainLookup::new(vec![ptr])));
+            }
+        }
+
+        debug!(domain = %domain, "Reverse name not found");
+        self.near_negative(&domain, name_exists).await
+    }
+
+    /// PTR for a reverse name pointing at the first of the zone's names whose
+    /// address records include the address
+    async fn synthesize_ptr(&self, query: &Name, zone: &ReverseZone) -> Option<Record> {
+        let addr = reverse::address_of(query)?;
+        let rtype = if addr.is_ipv4() { "A" } else { "AAAA" };
+        let now = SystemTime::now();
+
+        for target in &zone.names {
+            let records = match self.near_resolver.resolve(target, rtype).await {
+                Ok(records) => records,
+                Err(e) => {
+                    debug!(name = %target, error = %e, "No addresses to match");
+                    continue;
+                }
+            };
+            let matching = records
+                .iter()
+                .find(|r| r.value.parse::<IpAddr>().is_ok_and(|ip| ip == addr));
+            if let Some(record) = matching {
+                let mut ptr = Name::from_str(target).ok()?;
+                ptr.set_fqdn(true);
+                info!(address = %addr, name = %ptr, "Synthesized PTR from forward record");
+                return Some(Record::from_rdata(
+                    query.clone(),
+                    record.ttl_at(now),
+                    RData::PTR(PTR(ptr)),
+                ));
+            }
+        }
+        None
+    }
+
     /// Handle upstream DNS resolution
     async fn resolve_upstream(
         &self,
@@ -191,15 +461,29 @@ impl Authority for BlockchainAuthority {
         rtype: RecordType,
         _lookup_options: LookupOptions,
     ) -> LookupControlFlow<Self::Lookup> {
+        // The catalog asks for the origin's SOA to fill the authority section
+        // of a negative answer; give it the NEAR zone's instead of the root's.
+        // (Catalogs call `AuthorityObject::soa_secure`, which comes here
+        // rather than to `Authority::soa_secure`.)
+        if rtype == RecordType::SOA && name == self.origin() {
+            if let Some(soa) = soa::take_negative_soa() {
+                return LookupControlFlow::Break(Ok(BlockchainLookup::new(vec![soa])));
+            }
+        }
+
         let domain = name.to_string();
         info!(domain = %domain, record_type = ?rtype, "DNS lookup request");
 
-        // Extract TLD and check if it's a known NEAR TLD
-        if let Some(tld) = Self::extract_tld(name) {
-            if self.near_resolver.is_near_tld(&tld) {
-                debug!(tld = %tld, "TLD is a known NEAR TLD, resolving via blockchain");
-                return self.resolve_near(name, rtype).await;
-            }
+        // Reverse zones served from NEAR
+        if let Some(zone) = self.reverse_zone(&Name::from(name.clone())) {
+            debug!(domain = %domain, zone = %zone.origin(), "Reverse zone, resolving via blockchain");
+            return self.resolve_reverse(name, rtype, zone).await;
+        }
+
+        // Check if the TLD is a known NEAR TLD
+        if self.is_near_name(name) {
+            debug!(domain = %domain, "TLD is a known NEAR TLD, resolving via blockchain");
+            return self.resolve_near(name, rtype).await;
         }
 
         // Not a NEAR TLD, forward upstream
@@ -212,6 +496,18 @@ impl Authority for BlockchainAuthority {
         request: RequestInfo<'_>,
         lookup_options: LookupOptions,
     ) -> LookupControlFlow<Self::Lookup> {
+        if !self.is_allowed(&request) {
+            info!(
+                client = %request.src.ip(),
+                protocol = %request.protocol,
+                domain = %request.query.name(),
+                "Query refused by ACL"
+            );
+            return LookupControlFlow::Break(Err(hickory_server::authority::LookupError::from(
+                ResponseCode::Refused,
+            )));
+        }
+
         let name = request.query.name();
         let rtype = request.query.query_type();
         self.lookup(name, rtype, lookup_options).await
@@ -230,3 +526,133 @@ impl Authority for BlockchainAuthority {
         Err(ResponseCode::NotImp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{ContractInfo, DnsCache};
+    use crate::soa::ZoneSoaHandler;
+    use hickory_proto::op::{Message, Query};
+    use hickory_proto::serialize::binary::{BinDecodable, BinEncoder};
+    use hickory_proto::xfer::Protocol;
+    use hickory_server::authority::{AuthorityObject, Catalog, MessageResponse};
+    use hickory_server::server::{Request, RequestHandler, ResponseHandler, ResponseInfo};
+    use std::io;
+    use std::sync::Mutex;
+
+    /// Keeps the response the catalog sends
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Option<Message>>>);
+
+    #[async_trait]
+    impl ResponseHandler for Captured {
+        async fn send_response<'a>(
+            &mut self,
+            response: MessageResponse<
+                '_,
+                'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+            >,
+        ) -> io::Result<ResponseInfo> {
+            let mut bytes = Vec::new();
+            let info = response.destructive_emit(&mut BinEncoder::new(&mut bytes))?;
+            *self.0.lock().unwrap() = Some(Message::from_vec(&bytes)?);
+            Ok(info)
+        }
+    }
+
+    /// A catalog with `alice.near` at serial 42, answered from the cache alone
+    async fn catalog(acl: AclConfig) -> ZoneSoaHandler<Catalog> {
+        let cache = DnsCache::new();
+        let zone = "dns.alice.near".to_string();
+        let missing = ContractInfo {
+            exists: false,
+            code_hash: None,
+        };
+        let deployed = ContractInfo {
+            exists: true,
+            code_hash: None,
+        };
+        cache.insert_contract("dns.missing.alice.near".to_string(), missing).await;
+        cache.insert_contract(zone.clone(), deployed).await;
+        cache.insert_resolve_support(zone.clone(), true).await;
+        cache
+            .insert_resolution(zone.clone(), "missing".to_string(), "A".to_string(), None)
+            .await;
+        cache.insert_serial(zone.clone(), 42).await;
+        cache
+            .insert_records(zone, "@".to_string(), "SOA".to_string(), Vec::new())
+            .await;
+
+        let near_resolver = NearResolver::new("http://127.0.0.1:1", cache).unwrap();
+        let authority: Arc<dyn AuthorityObject> = Arc::new(BlockchainAuthority::new(
+            near_resolver,
+            UpstreamResolver::with_nameservers(&[]),
+            acl,
+            SoaConfig::default(),
+            ReverseConfig::default(),
+        ));
+        let mut catalog = Catalog::new();
+        catalog.upsert(LowerName::from(Name::root()), vec![authority]);
+        ZoneSoaHandler::new(catalog)
+    }
+
+    async fn query(
+        handler: &ZoneSoaHandler<Catalog>,
+        name: &str,
+        rtype: RecordType,
+        protocol: Protocol,
+    ) -> Message {
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_str(name).unwrap(), rtype));
+        let bytes = message.to_vec().unwrap();
+        let request = Request::new(
+            MessageRequest::from_bytes(&bytes).unwrap(),
+            "127.0.0.1:5353".parse().unwrap(),
+            protocol,
+        );
+
+        let captured = Captured::default();
+        handler.handle_request(&request, captured.clone()).await;
+        let response = captured.0.lock().unwrap().take();
+        response.expect("no response sent")
+    }
+
+    #[tokio::test]
+    async fn test_nxdomain_carries_zone_soa() {
+        let handler = catalog(AclConfig::default()).await;
+        let response =
+            query(&handler, "missing.alice.near.", RecordType::A, Protocol::Udp).await;
+
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+        assert!(response.answers().is_empty());
+        let soa = match response.name_servers() {
+            [record] => record,
+            other => panic!("expected one authority record, got {:?}", other),
+        };
+        assert_eq!(soa.name(), &Name::from_str("alice.near.").unwrap());
+        match soa.data() {
+            RData::SOA(soa) => assert_eq!(soa.serial(), 42),
+            other => panic!("expected SOA, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tcp_queries_need_no_transfer_permission() {
+        let acl = AclConfig {
+            transfer: Vec::new(),
+            ..AclConfig::default()
+        };
+        let handler = catalog(acl).await;
+
+        let response =
+            query(&handler, "missing.alice.near.", RecordType::A, Protocol::Tcp).await;
+        assert_eq!(response.response_code(), ResponseCode::NXDomain);
+
+        let response = query(&handler, "alice.near.", RecordType::AXFR, Protocol::Tcp).await;
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+    }
+}
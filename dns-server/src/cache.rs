@@ -1,10 +1,24 @@
 use moka::future::Cache;
-use std::time::Duration;
+use moka::Expiry;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::records::DnsRecord;
+use crate::records::{DnsRecord, Resolution};
 
-/// Cache for contract existence checks
-pub type ContractCache = Cache<String, bool>;
+/// A zone contract account as last fetched
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractInfo {
+    /// Whether the account exists and has code deployed
+    pub exists: bool,
+    /// Base58 hash of the code, when it exists and the hash is known
+    pub code_hash: Option<String>,
+}
+
+/// Cache for contract existence checks, with the code hash of the contract
+pub type ContractCache = Cache<String, ContractInfo>;
+
+/// Cache for per-contract feature support
+pub type SupportCache = Cache<String, bool>;
 
 /// Cache key for DNS records: (contract_id, dns_name, record_type)
 pub type RecordCacheKey = (String, String, String);
@@ -12,8 +26,73 @@ pub type RecordCacheKey = (String, String, String);
 /// Cache for DNS records
 pub type RecordCache = Cache<RecordCacheKey, Vec<DnsRecord>>;
 
+/// Cache for `dns_resolve` answers; `None` means the name doesn't exist
+pub type ResolutionCache = Cache<RecordCacheKey, Option<Resolution>>;
+
+/// Cache for zone serials, keyed by contract ID
+pub type SerialCache = Cache<String, u64>;
+
+/// Expires cached records no later than the first of them changes status
+///
+/// Applies on top of the cache's TTL, so a cached answer never outlives a
+/// record's `valid_until`, and is refetched once a record that is not yet
+/// active by the local clock reaches its `valid_from`. Contracts leave out
+/// records that aren't active at the block they are read at, so a record
+/// whose window opens while an answer is cached only shows up once that
+/// answer expires, up to the cache's TTL later.
+struct ValidityExpiry;
+
+impl ValidityExpiry {
+    fn earliest<'a>(records: impl Iterator<Item = &'a DnsRecord>) -> Option<Duration> {
+        let now = SystemTime::now();
+        records.filter_map(|r| r.time_to_change(now)).min()
+    }
+}
+
+impl Expiry<RecordCacheKey, Vec<DnsRecord>> for ValidityExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &RecordCacheKey,
+        records: &Vec<DnsRecord>,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Self::earliest(records.iter())
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &RecordCacheKey,
+        records: &Vec<DnsRecord>,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Self::earliest(records.iter())
+    }
+}
+
+impl Expiry<RecordCacheKey, Option<Resolution>> for ValidityExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &RecordCacheKey,
+        resolution: &Option<Resolution>,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Self::earliest(resolution.iter().flat_map(|r| &r.records))
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &RecordCacheKey,
+        resolution: &Option<Resolution>,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Self::earliest(resolution.iter().flat_map(|r| &r.records))
+    }
+}
+
 /// Configuration for cache TTLs
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CacheConfig {
     /// TTL for contract existence cache (default: 5 minutes)
     pub contract_ttl: Duration,
@@ -34,46 +113,140 @@ impl Default for CacheConfig {
 }
 
 /// DNS cache manager
+///
+/// Clones share the same underlying caches, so a reconfiguration through one
+/// handle is seen by all of them.
 #[derive(Clone)]
 pub struct DnsCache {
+    inner: Arc<RwLock<CacheInner>>,
+}
+
+struct CacheInner {
+    config: CacheConfig,
     /// Contract existence cache
-    pub contract: ContractCache,
+    contract: ContractCache,
+    /// Whether each contract has the `dns_resolve` view, expiring with contract existence
+    resolve_support: SupportCache,
+    /// Whether each contract has the `dns_resolve_many` view, expiring likewise
+    batch_support: SupportCache,
     /// DNS record cache
-    pub records: RecordCache,
+    records: RecordCache,
+    /// `dns_resolve` answer cache
+    resolutions: ResolutionCache,
+    /// Zone serial cache, expiring with the records
+    serials: SerialCache,
 }
 
-impl DnsCache {
-    /// Create a new cache with default configuration
-    pub fn new() -> Self {
-        Self::with_config(CacheConfig::default())
-    }
-
-    /// Create a new cache with custom configuration
-    pub fn with_config(config: CacheConfig) -> Self {
+impl CacheInner {
+    fn build(config: CacheConfig) -> Self {
         let contract = Cache::builder()
             .time_to_live(config.contract_ttl)
             .max_capacity(config.max_entries)
             .build();
 
+        let resolve_support = Cache::builder()
+            .time_to_live(config.contract_ttl)
+            .max_capacity(config.max_entries)
+            .build();
+
+        let batch_support = Cache::builder()
+            .time_to_live(config.contract_ttl)
+            .max_capacity(config.max_entries)
+            .build();
+
         let records = Cache::builder()
+            .time_to_live(config.default_record_ttl)
+            .expire_after(ValidityExpiry)
+            .max_capacity(config.max_entries)
+            .build();
+
+        let resolutions = Cache::builder()
+            .time_to_live(config.default_record_ttl)
+            .expire_after(ValidityExpiry)
+            .max_capacity(config.max_entries)
+            .build();
+
+        let serials = Cache::builder()
             .time_to_live(config.default_record_ttl)
             .max_capacity(config.max_entries)
             .build();
 
         Self {
+            config,
             contract,
+            resolve_support,
+            batch_support,
             records,
+            resolutions,
+            serials,
         }
     }
+}
+
+impl DnsCache {
+    /// Create a new cache with default configuration
+    pub fn new() -> Self {
+        Self::with_config(CacheConfig::default())
+    }
+
+    /// Create a new cache with custom configuration
+    pub fn with_config(config: CacheConfig) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(CacheInner::build(config))),
+        }
+    }
+
+    /// Apply a new configuration
+    ///
+    /// Cached entries are kept when the configuration is unchanged. Otherwise the
+    /// caches are rebuilt (moka can't resize or re-TTL a live cache) and `true`
+    /// is returned.
+    pub fn reconfigure(&self, config: CacheConfig) -> bool {
+        let mut inner = self.inner.write().expect("cache lock poisoned");
+        if inner.config == config {
+            return false;
+        }
+        *inner = CacheInner::build(config);
+        true
+    }
+
+    fn contract_cache(&self) -> ContractCache {
+        self.inner.read().expect("cache lock poisoned").contract.clone()
+    }
+
+    fn record_cache(&self) -> RecordCache {
+        self.inner.read().expect("cache lock poisoned").records.clone()
+    }
+
+    fn resolve_support_cache(&self) -> SupportCache {
+        self.inner.read().expect("cache lock poisoned").resolve_support.clone()
+    }
+
+    fn batch_support_cache(&self) -> SupportCache {
+        self.inner.read().expect("cache lock poisoned").batch_support.clone()
+    }
+
+    fn resolution_cache(&self) -> ResolutionCache {
+        self.inner.read().expect("cache lock poisoned").resolutions.clone()
+    }
+
+    fn serial_cache(&self) -> SerialCache {
+        self.inner.read().expect("cache lock poisoned").serials.clone()
+    }
 
     /// Check if a contract existence is cached
-    pub async fn get_contract(&self, contract_id: &str) -> Option<bool> {
-        self.contract.get(contract_id).await
+    pub async fn get_contract(&self, contract_id: &str) -> Option<ContractInfo> {
+        self.contract_cache().get(contract_id).await
     }
 
     /// Cache a contract existence result
-    pub async fn insert_contract(&self, contract_id: String, exists: bool) {
-        self.contract.insert(contract_id, exists).await;
+    pub async fn insert_contract(&self, contract_id: String, info: ContractInfo) {
+        self.contract_cache().insert(contract_id, info).await;
+    }
+
+    /// Forget every cached contract existence result
+    pub fn clear_contracts(&self) {
+        self.contract_cache().invalidate_all();
     }
 
     /// Get cached DNS records
@@ -83,7 +256,7 @@ impl DnsCache {
             dns_name.to_string(),
             record_type.to_string(),
         );
-        self.records.get(&key).await
+        self.record_cache().get(&key).await
     }
 
     /// Cache DNS records
@@ -95,7 +268,64 @@ impl DnsCache {
         records: Vec<DnsRecord>,
     ) {
         let key = (contract_id, dns_name, record_type);
-        self.records.insert(key, records).await;
+        self.record_cache().insert(key, records).await;
+    }
+
+    /// Check whether a contract is known to have the `dns_resolve` view
+    pub async fn get_resolve_support(&self, contract_id: &str) -> Option<bool> {
+        self.resolve_support_cache().get(contract_id).await
+    }
+
+    /// Cache whether a contract has the `dns_resolve` view
+    pub async fn insert_resolve_support(&self, contract_id: String, supported: bool) {
+        self.resolve_support_cache().insert(contract_id, supported).await;
+    }
+
+    /// Check whether a contract is known to have the `dns_resolve_many` view
+    pub async fn get_batch_support(&self, contract_id: &str) -> Option<bool> {
+        self.batch_support_cache().get(contract_id).await
+    }
+
+    /// Cache whether a contract has the `dns_resolve_many` view
+    pub async fn insert_batch_support(&self, contract_id: String, supported: bool) {
+        self.batch_support_cache().insert(contract_id, supported).await;
+    }
+
+    /// Get a cached `dns_resolve` answer
+    pub async fn get_resolution(
+        &self,
+        contract_id: &str,
+        dns_name: &str,
+        record_type: &str,
+    ) -> Option<Option<Resolution>> {
+        let key = (
+            contract_id.to_string(),
+            dns_name.to_string(),
+            record_type.to_string(),
+        );
+        self.resolution_cache().get(&key).await
+    }
+
+    /// Cache a `dns_resolve` answer
+    pub async fn insert_resolution(
+        &self,
+        contract_id: String,
+        dns_name: String,
+        record_type: String,
+        resolution: Option<Resolution>,
+    ) {
+        let key = (contract_id, dns_name, record_type);
+        self.resolution_cache().insert(key, resolution).await;
+    }
+
+    /// Get a cached zone serial
+    pub async fn get_serial(&self, contract_id: &str) -> Option<u64> {
+        self.serial_cache().get(contract_id).await
+    }
+
+    /// Cache a zone serial
+    pub async fn insert_serial(&self, contract_id: String, serial: u64) {
+        self.serial_cache().insert(contract_id, serial).await;
     }
 }
 
@@ -0,0 +1,224 @@
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::acl::AclConfig;
+use crate::cache::CacheConfig;
+use crate::resolver::near::NearResolver;
+use crate::resolver::verify::VerifyConfig;
+use crate::reverse::ReverseConfig;
+use crate::rrl::RrlConfig;
+use crate::soa::SoaConfig;
+
+/// Error type for configuration loading
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read {0}: {1}")]
+    Read(String, std::io::Error),
+    #[error("Failed to parse {0}: {1}")]
+    Parse(String, String),
+    #[error("Invalid upstream address: {0}")]
+    InvalidUpstream(String),
+    #[error("Invalid SOA settings: {0}")]
+    InvalidSoa(String),
+    #[error("Invalid verify settings: {0}")]
+    InvalidVerify(String),
+    #[error("Invalid reverse zone: {0}")]
+    InvalidReverse(String),
+}
+
+/// Runtime configuration that can be reloaded with SIGHUP
+///
+/// Loaded from the TOML file given with `--config`. Every field is optional;
+/// anything left out keeps its default.
+///
+/// ```toml
+/// tlds = ["near", "testnet"]
+/// upstreams = ["1.1.1.1", "9.9.9.9:53"]
+///
+/// [cache]
+/// contract_ttl_secs = 300
+/// record_ttl_secs = 300
+/// max_entries = 10000
+///
+/// [acl]
+/// recursion = ["10.0.0.0/8"]
+///
+/// [rrl]
+/// enabled = true
+///
+/// [soa]
+/// rname = "hostmaster.example.com."
+///
+/// [verify]
+/// code_hashes = ["9dV2RBQ6xRcnjhpMCvA8pGv9RdG2rP3YwsmCUXkmFk4p"]
+/// policy = "warn"
+///
+/// [[reverse.zones]]
+/// prefix = "203.0.113.0/24"
+/// contract = "dns.reverse.infra.near"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// TLDs resolved through NEAR contracts
+    pub tlds: Vec<String>,
+    /// Upstream nameservers as `ip` or `ip:port` (default: Google and Cloudflare)
+    pub upstreams: Vec<String>,
+    /// Cache sizing and lifetimes
+    pub cache: CacheSettings,
+    /// Client access lists
+    pub acl: AclConfig,
+    /// Response rate limiting
+    pub rrl: RrlConfig,
+    /// Defaults for synthesized zone SOA records
+    pub soa: SoaConfig,
+    /// Code hash checks of zone contracts
+    pub verify: VerifyConfig,
+    /// Reverse zones served from NEAR
+    pub reverse: ReverseConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tlds: NearResolver::DEFAULT_NEAR_TLDS
+                .iter()
+                .map(|tld| tld.to_string())
+                .collect(),
+            upstreams: Vec::new(),
+            cache: CacheSettings::default(),
+            acl: AclConfig::default(),
+            rrl: RrlConfig::default(),
+            soa: SoaConfig::default(),
+            verify: VerifyConfig::default(),
+            reverse: ReverseConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from a TOML file
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let display = path.display().to_string();
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Read(display.clone(), e))?;
+        let config: Config =
+            toml::from_str(&contents).map_err(|e| ConfigError::Parse(display, e.to_string()))?;
+
+        // Surface bad addresses at load time rather than on the first query
+        config.upstream_addrs()?;
+        config
+            .soa
+            .validate()
+            .map_err(|e| ConfigError::InvalidSoa(e.to_string()))?;
+        config.verify.validate().map_err(ConfigError::InvalidVerify)?;
+        config.reverse.validate().map_err(ConfigError::InvalidReverse)?;
+        Ok(config)
+    }
+
+    /// Parse the configured upstream nameservers, defaulting to port 53
+    pub fn upstream_addrs(&self) -> Result<Vec<SocketAddr>, ConfigError> {
+        self.upstreams
+            .iter()
+            .map(|upstream| {
+                upstream
+                    .parse::<SocketAddr>()
+                    .or_else(|_| upstream.parse().map(|ip| SocketAddr::new(ip, 53)))
+                    .map_err(|_| ConfigError::InvalidUpstream(upstream.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Cache settings as written in the config file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSettings {
+    /// TTL for contract existence cache, in seconds
+    pub contract_ttl_secs: u64,
+    /// Default TTL for record cache, in seconds
+    pub record_ttl_secs: u64,
+    /// Maximum entries in each cache
+    pub max_entries: u64,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        CacheConfig::default().into()
+    }
+}
+
+impl From<CacheConfig> for CacheSettings {
+    fn from(config: CacheConfig) -> Self {
+        Self {
+            contract_ttl_secs: config.contract_ttl.as_secs(),
+            record_ttl_secs: config.default_record_ttl.as_secs(),
+            max_entries: config.max_entries,
+        }
+    }
+}
+
+impl From<&CacheSettings> for CacheConfig {
+    fn from(settings: &CacheSettings) -> Self {
+        Self {
+            contract_ttl: Duration::from_secs(settings.contract_ttl_secs),
+            default_record_ttl: Duration::from_secs(settings.record_ttl_secs),
+            max_entries: settings.max_entries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::verify::Policy;
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config, Config::default());
+        assert!(config.tlds.contains(&"near".to_string()));
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            tlds = ["near"]
+            upstreams = ["1.1.1.1", "[2606:4700:4700::1111]:5353"]
+
+            [cache]
+            max_entries = 500
+
+            [verify.tlds]
+            near = "reject"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.tlds, vec!["near".to_string()]);
+        assert_eq!(config.cache.max_entries, 500);
+        assert_eq!(config.cache.record_ttl_secs, 300);
+        assert_eq!(config.verify.policy_for("dns.alice.near"), Policy::Reject);
+        assert_eq!(
+            config.upstream_addrs().unwrap(),
+            vec![
+                "1.1.1.1:53".parse().unwrap(),
+                "[2606:4700:4700::1111]:5353".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_fields_and_bad_upstreams() {
+        assert!(toml::from_str::<Config>("tld = [\"near\"]").is_err());
+
+        let config = Config {
+            upstreams: vec!["dns.google".to_string()],
+            ..Default::default()
+        };
+        assert!(config.upstream_addrs().is_err());
+    }
+}
@@ -0,0 +1,285 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::str::FromStr;
+
+/// Transport protocol served on a listener
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "udp" => Ok(Transport::Udp),
+            "tcp" => Ok(Transport::Tcp),
+            other => Err(format!("unknown protocol '{}' (expected udp or tcp)", other)),
+        }
+    }
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Udp => write!(f, "udp"),
+            Transport::Tcp => write!(f, "tcp"),
+        }
+    }
+}
+
+/// A single listen address with the protocols served on it
+///
+/// Parsed from `<addr>[/<proto>[,<proto>...]]`, for example `0.0.0.0:53`,
+/// `[::]:53/udp` or `10.0.0.1:53/udp,tcp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenerSpec {
+    pub addr: SocketAddr,
+    /// Protocols to serve; `None` means the server-wide default
+    pub transports: Option<Vec<Transport>>,
+}
+
+impl ListenerSpec {
+    /// Protocols to serve on this address, falling back to `default` when none were given
+    pub fn transports_or<'a>(&'a self, default: &'a [Transport]) -> &'a [Transport] {
+        self.transports.as_deref().unwrap_or(default)
+    }
+}
+
+impl FromStr for ListenerSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, transports) = match s.rsplit_once('/') {
+            Some((addr, protos)) => {
+                let transports = protos
+                    .split(',')
+                    .map(Transport::from_str)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if transports.is_empty() {
+                    return Err(format!("no protocols given for '{}'", addr));
+                }
+                (addr, Some(transports))
+            }
+            None => (s, None),
+        };
+
+        let addr = SocketAddr::from_str(addr)
+            .map_err(|e| format!("invalid listen address '{}': {}", addr, e))?;
+
+        Ok(Self { addr, transports })
+    }
+}
+
+impl fmt::Display for ListenerSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.addr)?;
+        if let Some(transports) = &self.transports {
+            let protos: Vec<String> = transports.iter().map(ToString::to_string).collect();
+            write!(f, "/{}", protos.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Create a socket for `addr`
+///
+/// IPv6 sockets are made v6-only so that `[::]:53` and `0.0.0.0:53` can be
+/// bound side by side for dual-stack operation.
+fn new_socket(addr: SocketAddr, ty: Type, protocol: Protocol) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(addr), ty, Some(protocol))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Bind `count` UDP sockets to `addr`
+///
+/// With more than one socket, each is bound with `SO_REUSEPORT` so the kernel
+/// spreads incoming datagrams across them and every socket gets its own
+/// receive loop. If `addr` uses port 0, all sockets share the port picked for
+/// the first one.
+pub fn bind_udp(addr: SocketAddr, count: usize) -> io::Result<Vec<UdpSocket>> {
+    let reuse_port = count > 1;
+    if reuse_port && !cfg!(unix) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "multiple UDP sockets per address require SO_REUSEPORT (unix only)",
+        ));
+    }
+
+    let mut addr = addr;
+    let mut sockets = Vec::with_capacity(count.max(1));
+    for _ in 0..count.max(1) {
+        let socket = new_socket(addr, Type::DGRAM, Protocol::UDP)?;
+        #[cfg(unix)]
+        if reuse_port {
+            socket.set_reuse_port(true)?;
+        }
+        socket.bind(&addr.into())?;
+
+        if addr.port() == 0 {
+            if let Some(local) = socket.local_addr()?.as_socket() {
+                addr = local;
+            }
+        }
+        sockets.push(socket.into());
+    }
+
+    Ok(sockets)
+}
+
+/// Bind a TCP listener to `addr`
+pub fn bind_tcp(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = new_socket(addr, Type::STREAM, Protocol::TCP)?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+/// A socket passed in by the service manager
+pub enum ActivatedSocket {
+    Udp(UdpSocket),
+    Tcp(TcpListener),
+}
+
+/// First file descriptor passed by systemd (`SD_LISTEN_FDS_START`)
+#[cfg(unix)]
+const LISTEN_FDS_START: i32 = 3;
+
+/// Number of sockets passed to this process via systemd socket activation
+///
+/// Follows `sd_listen_fds(3)`: the sockets are only ours if `LISTEN_PID`
+/// matches our PID.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn listen_fds_count(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> io::Result<usize> {
+    let (Some(listen_pid), Some(listen_fds)) = (listen_pid, listen_fds) else {
+        return Ok(0);
+    };
+
+    let listen_pid: u32 = listen_pid
+        .trim()
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid LISTEN_PID"))?;
+    if listen_pid != pid {
+        return Ok(0);
+    }
+
+    listen_fds
+        .trim()
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid LISTEN_FDS"))
+}
+
+/// Take the sockets passed via systemd socket activation (`LISTEN_FDS`)
+///
+/// Returns an empty list when the process wasn't socket-activated. The
+/// activation variables are removed so child processes don't inherit them.
+#[cfg(unix)]
+pub fn from_systemd() -> io::Result<Vec<ActivatedSocket>> {
+    use std::os::fd::FromRawFd;
+
+    let count = listen_fds_count(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )?;
+
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    let mut sockets = Vec::with_capacity(count);
+    for fd in LISTEN_FDS_START..LISTEN_FDS_START + count as i32 {
+        // SAFETY: systemd hands these descriptors to us and nothing else in the
+        // process has claimed them yet.
+        let socket = unsafe { Socket::from_raw_fd(fd) };
+        socket.set_cloexec(true)?;
+        socket.set_nonblocking(true)?;
+
+        let activated = match socket.r#type()? {
+            Type::DGRAM => ActivatedSocket::Udp(socket.into()),
+            Type::STREAM => ActivatedSocket::Tcp(socket.into()),
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("unsupported socket type {:?} passed on fd {}", other, fd),
+                ))
+            }
+        };
+        sockets.push(activated);
+    }
+
+    Ok(sockets)
+}
+
+/// Take the sockets passed via systemd socket activation (`LISTEN_FDS`)
+#[cfg(not(unix))]
+pub fn from_systemd() -> io::Result<Vec<ActivatedSocket>> {
+    Ok(Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_address() {
+        let spec: ListenerSpec = "0.0.0.0:53".parse().unwrap();
+        assert_eq!(spec.addr, "0.0.0.0:53".parse().unwrap());
+        assert_eq!(spec.transports, None);
+        assert_eq!(spec.transports_or(&[Transport::Udp]), &[Transport::Udp]);
+    }
+
+    #[test]
+    fn test_parse_address_with_protocols() {
+        let spec: ListenerSpec = "[::1]:5355/udp,TCP".parse().unwrap();
+        assert_eq!(spec.addr, "[::1]:5355".parse().unwrap());
+        assert_eq!(spec.transports, Some(vec![Transport::Udp, Transport::Tcp]));
+        assert_eq!(spec.to_string(), "[::1]:5355/udp,tcp");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!("localhost:53".parse::<ListenerSpec>().is_err());
+        assert!("127.0.0.1:53/quic".parse::<ListenerSpec>().is_err());
+        assert!("127.0.0.1:53/".parse::<ListenerSpec>().is_err());
+    }
+
+    #[test]
+    fn test_listen_fds_count() {
+        assert_eq!(listen_fds_count(None, None, 42).unwrap(), 0);
+        assert_eq!(listen_fds_count(Some("42"), Some("2"), 42).unwrap(), 2);
+        // Sockets meant for another process (e.g. our parent) are ignored
+        assert_eq!(listen_fds_count(Some("41"), Some("2"), 42).unwrap(), 0);
+        assert!(listen_fds_count(Some("42"), Some("two"), 42).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bind_udp_reuse_port() {
+        let sockets = bind_udp("127.0.0.1:0".parse().unwrap(), 4).unwrap();
+        assert_eq!(sockets.len(), 4);
+
+        let port = sockets[0].local_addr().unwrap().port();
+        assert!(sockets.iter().all(|s| s.local_addr().unwrap().port() == port));
+    }
+
+    #[test]
+    fn test_bind_dual_stack() {
+        let v4 = bind_tcp("127.0.0.1:0".parse().unwrap()).unwrap();
+        let port = v4.local_addr().unwrap().port();
+
+        // Loopback-only hosts may lack IPv6; only check that v6-only doesn't clash with v4
+        if let Ok(v6) = bind_tcp(SocketAddr::new("::1".parse().unwrap(), port)) {
+            assert_eq!(v6.local_addr().unwrap().port(), port);
+        }
+    }
+}
@@ -1,42 +1,81 @@
+mod acl;
 mod authority;
 mod cache;
+mod config;
+mod listener;
+mod privileges;
 mod records;
 mod resolver;
+mod reverse;
+mod rrl;
+mod shutdown;
+mod soa;
 
 use authority::BlockchainAuthority;
 use cache::DnsCache;
+use config::Config;
+use listener::{ActivatedSocket, ListenerSpec, Transport};
+use privileges::PrivilegeConfig;
 use resolver::near::NearResolver;
 use resolver::upstream::UpstreamResolver;
+use rrl::{RateLimitedHandler, RateLimiter};
+use shutdown::{DrainState, DrainingHandler};
+use soa::ZoneSoaHandler;
 
 use clap::Parser;
 use hickory_server::authority::{AuthorityObject, Catalog};
 use hickory_server::ServerFuture;
-use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::{TcpListener, UdpSocket};
-use tracing::{error, info};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 /// NEAR DNS Server - Resolve .near and other blockchain TLDs via NEAR Protocol
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Address to bind the DNS server to
+    /// Address to bind the DNS server to, optionally with the protocols to serve on it
+    /// (e.g. `0.0.0.0:53`, `[::]:53/udp`, `10.0.0.1:53/udp,tcp`). May be repeated.
     #[arg(short, long, default_value = "127.0.0.1:5355")]
-    bind: SocketAddr,
+    bind: Vec<ListenerSpec>,
 
     /// NEAR RPC URL
     #[arg(short, long, default_value = "https://rpc.testnet.near.org")]
     rpc_url: String,
 
-    /// Enable TCP support
+    /// Enable TCP support on listeners that don't list their protocols
     #[arg(long, default_value = "true")]
     tcp: bool,
 
+    /// Number of UDP sockets to open per address (uses SO_REUSEPORT when > 1)
+    #[arg(long, default_value = "1")]
+    udp_sockets: usize,
+
     /// TCP connection timeout in seconds
     #[arg(long, default_value = "30")]
     tcp_timeout: u64,
+
+    /// Path to a TOML config file (TLDs, upstreams, cache limits); re-read on SIGHUP
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+
+    /// Seconds to wait for in-flight queries to finish on SIGTERM/SIGINT
+    #[arg(long, default_value = "10")]
+    shutdown_timeout: u64,
+
+    /// User (name or UID) to switch to after binding sockets
+    #[arg(long)]
+    user: Option<String>,
+
+    /// Group (name or GID) to switch to after binding sockets (default: the user's group)
+    #[arg(long)]
+    group: Option<String>,
+
+    /// Directory to chroot into after binding sockets
+    #[arg(long)]
+    chroot: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -50,59 +89,174 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
     info!("Starting NEAR DNS Server");
-    info!("Bind address: {}", args.bind);
+    for spec in &args.bind {
+        info!("Bind address: {}", spec);
+    }
     info!("NEAR RPC URL: {}", args.rpc_url);
 
+    // Load the reloadable part of the configuration
+    let config = match &args.config {
+        Some(path) => {
+            info!("Config file: {}", path.display());
+            Config::load(path)?
+        }
+        None => Config::default(),
+    };
+
     // Create the cache
-    let cache = DnsCache::new();
+    let cache = DnsCache::with_config((&config.cache).into());
 
     // Create the NEAR resolver
     let near_resolver = NearResolver::new(&args.rpc_url, cache)
         .map_err(|e| format!("Failed to create NEAR resolver: {}", e))?;
+    near_resolver.set_tlds(&config.tlds);
+    near_resolver.set_verify(&config.verify);
 
     // Create the upstream resolver
-    let upstream_resolver = UpstreamResolver::new();
+    let upstream_resolver = UpstreamResolver::with_nameservers(&config.upstream_addrs()?);
 
     // Create the blockchain authority
-    let authority = BlockchainAuthority::new(near_resolver, upstream_resolver);
+    let authority = Arc::new(BlockchainAuthority::new(
+        near_resolver,
+        upstream_resolver,
+        config.acl.clone(),
+        config.soa.clone(),
+        config.reverse.clone(),
+    ));
 
     // Create a catalog and add our authority for the root zone
     let mut catalog = Catalog::new();
     
     // Register the authority for all queries (root zone)
-    let authority: Arc<dyn AuthorityObject> = Arc::new(authority);
+    let authority_object: Arc<dyn AuthorityObject> = authority.clone();
     catalog.upsert(
         hickory_proto::rr::LowerName::from(hickory_proto::rr::Name::root()),
-        vec![authority],
+        vec![authority_object],
     );
 
-    // Create the server
-    let mut server = ServerFuture::new(catalog);
+    // Create the server, tracking in-flight requests so shutdown can drain them
+    let drain_state = Arc::new(DrainState::default());
+    // Response rate limiting sits in front of the catalog so limited answers are never sent
+    let limiter = Arc::new(RateLimiter::new(config.rrl.clone()));
+    tokio::spawn(rrl::log_metrics(limiter.clone(), Duration::from_secs(60)));
+    let handler = RateLimitedHandler::new(ZoneSoaHandler::new(catalog), limiter.clone());
+    let mut server = ServerFuture::new(DrainingHandler::new(handler, drain_state.clone()));
 
-    // Bind UDP socket
-    let udp_socket = UdpSocket::bind(args.bind).await?;
-    info!("UDP socket bound to {}", args.bind);
-    server.register_socket(udp_socket);
+    // Sockets passed by systemd take precedence over --bind
+    let activated = listener::from_systemd()?;
+    let socket_activated = !activated.is_empty();
+    if !socket_activated {
+        let default_transports: &[Transport] = if args.tcp {
+            &[Transport::Udp, Transport::Tcp]
+        } else {
+            &[Transport::Udp]
+        };
 
-    // Optionally bind TCP listener
-    if args.tcp {
-        let tcp_listener = TcpListener::bind(args.bind).await?;
-        info!("TCP listener bound to {}", args.bind);
-        server.register_listener(tcp_listener, Duration::from_secs(args.tcp_timeout));
+        for spec in &args.bind {
+            for transport in spec.transports_or(default_transports) {
+                match transport {
+                    Transport::Udp => {
+                        for socket in listener::bind_udp(spec.addr, args.udp_sockets)? {
+                            server.register_socket_std(socket)?;
+                        }
+                        info!("UDP socket(s) bound to {} (x{})", spec.addr, args.udp_sockets.max(1));
+                    }
+                    Transport::Tcp => {
+                        let tcp_listener = listener::bind_tcp(spec.addr)?;
+                        info!("TCP listener bound to {}", spec.addr);
+                        server.register_listener_std(tcp_listener, Duration::from_secs(args.tcp_timeout))?;
+                    }
+                }
+            }
+        }
+    } else {
+        info!("Using {} socket(s) from systemd, ignoring --bind", activated.len());
+        for socket in activated {
+            match socket {
+                ActivatedSocket::Udp(socket) => {
+                    info!("UDP socket activated on {}", socket.local_addr()?);
+                    server.register_socket_std(socket)?;
+                }
+                ActivatedSocket::Tcp(tcp_listener) => {
+                    info!("TCP listener activated on {}", tcp_listener.local_addr()?);
+                    server.register_listener_std(tcp_listener, Duration::from_secs(args.tcp_timeout))?;
+                }
+            }
+        }
     }
 
+    // All sockets are bound; give up root before serving any queries
+    privileges::drop_privileges(&PrivilegeConfig {
+        user: args.user.clone(),
+        group: args.group.clone(),
+        chroot: args.chroot.clone(),
+    })
+    .map_err(|e| format!("Failed to drop privileges: {}", e))?;
+
     info!("DNS server is running. Press Ctrl+C to stop.");
-    info!("Test with: dig @{} <domain> A", args.bind);
+    if let Some(spec) = args.bind.first().filter(|_| !socket_activated) {
+        info!("Test with: dig @{} <domain> A", spec.addr);
+    }
 
-    // Run the server
-    match server.block_until_done().await {
-        Ok(_) => {
-            info!("Server shutdown gracefully");
-            Ok(())
-        }
-        Err(e) => {
-            error!("Server error: {}", e);
-            Err(e.into())
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sighup = signal(SignalKind::hangup())?;
+
+    // Run the server until it fails or we're asked to stop
+    loop {
+        tokio::select! {
+            result = server.block_until_done() => {
+                return match result {
+                    Ok(_) => {
+                        info!("Server shutdown gracefully");
+                        Ok(())
+                    }
+                    Err(e) => {
+                        error!("Server error: {}", e);
+                        Err(e.into())
+                    }
+                };
+            }
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM");
+                break;
+            }
+            _ = sigint.recv() => {
+                info!("Received SIGINT");
+                break;
+            }
+            _ = sighup.recv() => {
+                let Some(path) = &args.config else {
+                    warn!("Received SIGHUP but no --config file was given, nothing to reload");
+                    continue;
+                };
+                info!("Received SIGHUP, reloading {}", path.display());
+                match Config::load(path) {
+                    Ok(config) => match authority.reload(&config) {
+                        Ok(()) => {
+                            limiter.reconfigure(config.rrl.clone());
+                            info!("Configuration reloaded");
+                        }
+                        Err(e) => error!("Failed to apply configuration, keeping the old one: {}", e),
+                    },
+                    Err(e) => error!("Failed to reload configuration, keeping the old one: {}", e),
+                }
+            }
         }
     }
+
+    // Stop answering new queries, let the accepted ones finish, then close the sockets
+    let drained = drain_state
+        .drain(Duration::from_secs(args.shutdown_timeout))
+        .await;
+    if let Err(e) = server.shutdown_gracefully().await {
+        error!("Error while stopping listeners: {}", e);
+    }
+
+    if drained {
+        info!("Server shutdown gracefully");
+    } else {
+        warn!("Server shut down with queries still in flight");
+    }
+    Ok(())
 }
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+use tracing::info;
+
+/// Error type for privilege dropping
+#[derive(Debug, thiserror::Error)]
+pub enum PrivilegeError {
+    #[error("Unknown user: {0}")]
+    UnknownUser(String),
+    #[error("Unknown group: {0}")]
+    UnknownGroup(String),
+    #[error("Failed to {0}: {1}")]
+    Syscall(&'static str, String),
+    #[cfg(not(unix))]
+    #[error("Privilege dropping is not supported on this platform")]
+    Unsupported,
+}
+
+/// Identity to switch to once all sockets are bound
+#[derive(Debug, Clone, Default)]
+pub struct PrivilegeConfig {
+    /// User name or numeric UID to run as
+    pub user: Option<String>,
+    /// Group name or numeric GID to run as (default: the user's primary group)
+    pub group: Option<String>,
+    /// Directory to chroot into before switching user
+    pub chroot: Option<PathBuf>,
+}
+
+impl PrivilegeConfig {
+    /// Whether any privilege change was requested
+    pub fn is_empty(&self) -> bool {
+        self.user.is_none() && self.group.is_none() && self.chroot.is_none()
+    }
+}
+
+/// Chroot and switch to the configured user/group
+///
+/// Must be called after every privileged socket has been bound. Users and
+/// groups are looked up before the chroot, so the chroot doesn't need its own
+/// `/etc/passwd`; it does need whatever the RPC client reads at runtime (e.g.
+/// `/etc/resolv.conf` and CA certificates).
+#[cfg(unix)]
+pub fn drop_privileges(config: &PrivilegeConfig) -> Result<(), PrivilegeError> {
+    use nix::unistd::{self, Gid, Uid};
+
+    if config.is_empty() {
+        return Ok(());
+    }
+
+    let user = match &config.user {
+        Some(name) => Some(lookup_user(name)?),
+        None => None,
+    };
+
+    let gid = match (&config.group, &user) {
+        (Some(name), _) => Some(lookup_group(name)?),
+        (None, Some(user)) => Some(user.gid),
+        (None, None) => None,
+    };
+
+    if let Some(dir) = &config.chroot {
+        unistd::chroot(dir).map_err(|e| PrivilegeError::Syscall("chroot", e.to_string()))?;
+        unistd::chdir("/").map_err(|e| PrivilegeError::Syscall("chdir", e.to_string()))?;
+        info!(dir = %dir.display(), "Changed root directory");
+    }
+
+    if let Some(gid) = gid {
+        unistd::setgroups(&[gid]).map_err(|e| PrivilegeError::Syscall("setgroups", e.to_string()))?;
+        unistd::setgid(gid).map_err(|e| PrivilegeError::Syscall("setgid", e.to_string()))?;
+    }
+
+    if let Some(user) = &user {
+        unistd::setuid(user.uid).map_err(|e| PrivilegeError::Syscall("setuid", e.to_string()))?;
+
+        // Make sure root can't be regained
+        if !user.uid.is_root() && unistd::setuid(Uid::from_raw(0)).is_ok() {
+            return Err(PrivilegeError::Syscall(
+                "drop root",
+                "setuid(0) still succeeds".to_string(),
+            ));
+        }
+    }
+
+    info!(
+        uid = %Uid::current(),
+        gid = %Gid::current(),
+        "Dropped privileges"
+    );
+    Ok(())
+}
+
+/// Look up a user by name or numeric UID
+#[cfg(unix)]
+fn lookup_user(name: &str) -> Result<nix::unistd::User, PrivilegeError> {
+    use nix::unistd::{Uid, User};
+
+    let user = match name.parse::<u32>() {
+        Ok(uid) => User::from_uid(Uid::from_raw(uid)),
+        Err(_) => User::from_name(name),
+    };
+    user.map_err(|e| PrivilegeError::Syscall("look up user", e.to_string()))?
+        .ok_or_else(|| PrivilegeError::UnknownUser(name.to_string()))
+}
+
+/// Look up a group by name or numeric GID
+#[cfg(unix)]
+fn lookup_group(name: &str) -> Result<nix::unistd::Gid, PrivilegeError> {
+    use nix::unistd::{Gid, Group};
+
+    if let Ok(gid) = name.parse::<u32>() {
+        return Ok(Gid::from_raw(gid));
+    }
+    Group::from_name(name)
+        .map_err(|e| PrivilegeError::Syscall("look up group", e.to_string()))?
+        .map(|group| group.gid)
+        .ok_or_else(|| PrivilegeError::UnknownGroup(name.to_string()))
+}
+
+/// Chroot and switch to the configured user/group
+#[cfg(not(unix))]
+pub fn drop_privileges(config: &PrivilegeConfig) -> Result<(), PrivilegeError> {
+    if config.is_empty() {
+        Ok(())
+    } else {
+        Err(PrivilegeError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config_is_noop() {
+        let config = PrivilegeConfig::default();
+        assert!(config.is_empty());
+        assert!(drop_privileges(&config).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unknown_user() {
+        let config = PrivilegeConfig {
+            user: Some("near-dns-no-such-user".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            drop_privileges(&config),
+            Err(PrivilegeError::UnknownUser(_))
+        ));
+    }
+}
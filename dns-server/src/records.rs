@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use hickory_proto::rr::rdata::{A, AAAA, CNAME, MX, NS, PTR, SOA, SRV, TXT};
 use hickory_proto::rr::{Name, RData, Record, RecordType};
@@ -13,6 +14,41 @@ pub struct DnsRecord {
     pub ttl: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priority: Option<u16>,
+    /// Time the record starts being served, in nanoseconds since the Unix epoch
+    #[serde(default, with = "u64_string", skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<u64>,
+    /// Time the record stops being served, in nanoseconds since the Unix epoch
+    #[serde(default, with = "u64_string", skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<u64>,
+}
+
+/// Optional `u64` written as a JSON string, as the contract's `U64` is
+mod u64_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(v) => serializer.serialize_str(&v.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| s.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// Answer from the contract's `dns_resolve` view
+#[derive(Debug, Clone, Deserialize)]
+pub struct Resolution {
+    /// Name the records belong to: the queried name or the wildcard that matched it
+    pub name: String,
+    /// Records of the queried type; empty if the name only has other types
+    pub records: Vec<DnsRecord>,
+    /// Whether the name has records of other types
+    pub other_types: bool,
 }
 
 /// Error type for record conversion
@@ -31,6 +67,38 @@ pub enum RecordConversionError {
 }
 
 impl DnsRecord {
+    /// Whether the record is within its validity window at `now`
+    pub fn is_active(&self, now: SystemTime) -> bool {
+        let now = unix_nanos(now);
+        self.valid_from.is_none_or(|from| now >= from)
+            && self.valid_until.is_none_or(|until| now < until)
+    }
+
+    /// Time left before the record expires, if it has an expiry
+    pub fn time_to_expiry(&self, now: SystemTime) -> Option<Duration> {
+        self.valid_until
+            .map(|until| Duration::from_nanos(until.saturating_sub(unix_nanos(now))))
+    }
+
+    /// Time left before the record's status changes: until it becomes active
+    /// if it isn't yet, or else until it expires
+    pub fn time_to_change(&self, now: SystemTime) -> Option<Duration> {
+        let nanos = unix_nanos(now);
+        self.valid_from
+            .filter(|from| *from > nanos)
+            .map(|from| Duration::from_nanos(from - nanos))
+            .or_else(|| self.time_to_expiry(now))
+    }
+
+    /// TTL to serve at `now`: the record's TTL, capped so caches drop the
+    /// record by the time it expires
+    pub fn ttl_at(&self, now: SystemTime) -> u32 {
+        match self.time_to_expiry(now) {
+            Some(left) => self.ttl.min(left.as_secs().try_into().unwrap_or(u32::MAX)),
+            None => self.ttl,
+        }
+    }
+
     /// Convert a contract DnsRecord to hickory RData
     pub fn to_rdata(&self, origin: &Name) -> Result<RData, RecordConversionError> {
         let rtype = self.record_type.to_uppercase();
@@ -67,7 +135,7 @@ impl DnsRecord {
                 Ok(RData::MX(MX::new(preference, exchange)))
             }
             "TXT" => {
-                Ok(RData::TXT(TXT::new(vec![self.value.clone()])))
+                Ok(RData::TXT(TXT::new(split_txt(&self.value))))
             }
             "SRV" => {
                 // Format: "weight port target"
@@ -127,6 +195,12 @@ impl DnsRecord {
     }
 }
 
+/// Nanoseconds since the Unix epoch, the unit of the contract's timestamps
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos().try_into().unwrap_or(u64::MAX))
+}
+
 /// Parse a domain name, handling relative names by appending origin
 fn parse_domain_name(name: &str, origin: &Name) -> Result<Name, RecordConversionError> {
     // Handle special case "@" which means the origin itself
@@ -146,6 +220,24 @@ fn parse_domain_name(name: &str, origin: &Name) -> Result<Name, RecordConversion
     }
 }
 
+/// Split a TXT value into character-strings of at most 255 bytes
+fn split_txt(value: &str) -> Vec<String> {
+    if value.is_empty() {
+        return vec![String::new()];
+    }
+    let mut strings = Vec::new();
+    let mut start = 0;
+    while start < value.len() {
+        let mut end = (start + 255).min(value.len());
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        strings.push(value[start..end].to_string());
+        start = end;
+    }
+    strings
+}
+
 /// Convert RecordType enum to string
 pub fn record_type_to_string(rt: RecordType) -> String {
     match rt {
@@ -175,6 +267,8 @@ mod tests {
             value: "192.168.1.1".to_string(),
             ttl: 300,
             priority: None,
+            valid_from: None,
+            valid_until: None,
         };
         let origin = Name::from_str("example.near.").unwrap();
         let rdata = record.to_rdata(&origin).unwrap();
@@ -188,12 +282,65 @@ mod tests {
             value: "Hello from NEAR!".to_string(),
             ttl: 300,
             priority: None,
+            valid_from: None,
+            valid_until: None,
         };
         let origin = Name::from_str("example.near.").unwrap();
         let rdata = record.to_rdata(&origin).unwrap();
         assert!(matches!(rdata, RData::TXT(_)));
     }
 
+    #[test]
+    fn test_resolution_from_contract_json() {
+        let resolution: Resolution = serde_json::from_str(
+            r#"{"name":"*.api","records":[{"record_type":"A","value":"10.0.0.2","ttl":300}],"other_types":false}"#,
+        )
+        .unwrap();
+        assert_eq!(resolution.name, "*.api");
+        assert_eq!(resolution.records[0].value, "10.0.0.2");
+        assert!(!resolution.other_types);
+    }
+
+    #[test]
+    fn test_validity_window() {
+        let record: DnsRecord = serde_json::from_str(
+            r#"{"record_type":"TXT","value":"token","ttl":300,"valid_from":"1000000000000","valid_until":"1120000000000"}"#,
+        )
+        .unwrap();
+        let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+
+        assert!(!record.is_active(at(999)));
+        assert!(record.is_active(at(1000)));
+        assert!(!record.is_active(at(1120)));
+
+        // The TTL never outlives the record
+        assert_eq!(record.ttl_at(at(1000)), 120);
+        assert_eq!(record.ttl_at(at(1110)), 10);
+        assert_eq!(record.ttl_at(at(2000)), 0);
+
+        // Pending records change when they become active, then at expiry
+        assert_eq!(
+            record.time_to_change(at(990)),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            record.time_to_change(at(1000)),
+            Some(Duration::from_secs(120))
+        );
+
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.contains(r#""valid_until":"1120000000000""#));
+    }
+
+    #[test]
+    fn test_long_txt_is_split() {
+        let value = "k=rsa; p=".to_string() + &"A".repeat(600);
+        let strings = split_txt(&value);
+        assert_eq!(strings.len(), 3);
+        assert!(strings.iter().all(|s| s.len() <= 255));
+        assert_eq!(strings.concat(), value);
+    }
+
     #[test]
     fn test_mx_record_conversion() {
         let record = DnsRecord {
@@ -201,6 +348,8 @@ mod tests {
             value: "mail.example.com.".to_string(),
             ttl: 300,
             priority: Some(10),
+            valid_from: None,
+            valid_until: None,
         };
         let origin = Name::from_str("example.near.").unwrap();
         let rdata = record.to_rdata(&origin).unwrap();
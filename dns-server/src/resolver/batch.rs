@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use crate::records::Resolution;
+use crate::resolver::near::ResolverError;
+
+/// How long the first lookup for a contract waits for others to join its batch
+pub const BATCH_WINDOW: Duration = Duration::from_millis(2);
+/// Most lookups sent in one call (the contract's `MAX_QUERIES`)
+pub const MAX_BATCH: usize = 50;
+
+/// A (name, record type) lookup
+pub type Query = (String, String);
+
+/// Results for a batch, one per query and in the same order
+pub type BatchResult = Result<Vec<Option<Resolution>>, ResolverError>;
+
+/// Runs one batch of lookups against a contract
+pub type FetchFn = Arc<
+    dyn Fn(String, Vec<Query>) -> Pin<Box<dyn Future<Output = BatchResult> + Send>> + Send + Sync,
+>;
+
+type Waiter = oneshot::Sender<Result<Option<Resolution>, ResolverError>>;
+
+/// Groups concurrent lookups for the same contract into one call
+///
+/// The first lookup for a contract opens a batch and flushes it after
+/// [`BATCH_WINDOW`]; lookups arriving in the meantime join it, and a batch
+/// that reaches [`MAX_BATCH`] distinct queries is flushed at once. Identical
+/// queries in a batch share one slot.
+#[derive(Clone)]
+pub struct Batcher {
+    fetch: FetchFn,
+    window: Duration,
+    pending: Arc<Mutex<HashMap<String, Batch>>>,
+}
+
+#[derive(Default)]
+struct Batch {
+    queries: Vec<Query>,
+    waiters: Vec<Vec<Waiter>>,
+}
+
+impl Batcher {
+    pub fn new(fetch: FetchFn) -> Self {
+        Self::with_window(fetch, BATCH_WINDOW)
+    }
+
+    pub fn with_window(fetch: FetchFn, window: Duration) -> Self {
+        Self {
+            fetch,
+            window,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Look up one name and type, batched with other lookups for the contract
+    pub async fn resolve(
+        &self,
+        contract_id: &str,
+        dns_name: &str,
+        record_type: &str,
+    ) -> Result<Option<Resolution>, ResolverError> {
+        let (tx, rx) = oneshot::channel();
+        let query = (dns_name.to_string(), record_type.to_string());
+
+        let (opened, full) = {
+            let mut pending = self.pending.lock().expect("batch lock poisoned");
+            let opened = !pending.contains_key(contract_id);
+            let batch = pending.entry(contract_id.to_string()).or_default();
+            match batch.queries.iter().position(|q| *q == query) {
+                Some(i) => batch.waiters[i].push(tx),
+                None => {
+                    batch.queries.push(query);
+                    batch.waiters.push(vec![tx]);
+                }
+            }
+            let full = batch.queries.len() >= MAX_BATCH;
+            (opened, full.then(|| pending.remove(contract_id)).flatten())
+        };
+
+        // Flushing runs in its own task so a cancelled lookup can't strand
+        // the others waiting on its batch
+        if let Some(batch) = full {
+            tokio::spawn(Self::flush(
+                self.fetch.clone(),
+                contract_id.to_string(),
+                batch,
+            ));
+        } else if opened {
+            let this = self.clone();
+            let contract_id = contract_id.to_string();
+            tokio::spawn(async move {
+                tokio::time::sleep(this.window).await;
+                let batch = this
+                    .pending
+                    .lock()
+                    .expect("batch lock poisoned")
+                    .remove(&contract_id);
+                if let Some(batch) = batch {
+                    Self::flush(this.fetch.clone(), contract_id, batch).await;
+                }
+            });
+        }
+
+        rx.await
+            .unwrap_or_else(|_| Err(ResolverError::RpcError("Batch was dropped".to_string())))
+    }
+
+    async fn flush(fetch: FetchFn, contract_id: String, batch: Batch) {
+        debug!(contract_id = %contract_id, queries = batch.queries.len(), "Flushing batch");
+        let count = batch.queries.len();
+        let result = fetch(contract_id, batch.queries).await.and_then(|results| {
+            if results.len() == count {
+                Ok(results)
+            } else {
+                Err(ResolverError::RpcError(format!(
+                    "Expected {} results, got {}",
+                    count,
+                    results.len()
+                )))
+            }
+        });
+
+        match result {
+            Ok(results) => {
+                for (waiters, resolution) in batch.waiters.into_iter().zip(results) {
+                    for waiter in waiters {
+                        let _ = waiter.send(Ok(resolution.clone()));
+                    }
+                }
+            }
+            Err(e) => {
+                for waiter in batch.waiters.into_iter().flatten() {
+                    let _ = waiter.send(Err(e.clone()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fetch that answers every query with a resolution named after it and
+    /// records the size of each batch
+    fn counting_fetch(batches: Arc<Mutex<Vec<usize>>>) -> FetchFn {
+        Arc::new(move |_contract_id, queries: Vec<Query>| {
+            batches.lock().unwrap().push(queries.len());
+            Box::pin(async move {
+                Ok(queries
+                    .into_iter()
+                    .map(|(name, record_type)| {
+                        Some(Resolution {
+                            name: format!("{}/{}", name, record_type),
+                            records: Vec::new(),
+                            other_types: false,
+                        })
+                    })
+                    .collect())
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_lookups_share_a_batch() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let batcher =
+            Batcher::with_window(counting_fetch(batches.clone()), Duration::from_millis(20));
+
+        let (a, aaaa, a_again) = tokio::join!(
+            batcher.resolve("dns.alice.near", "www", "A"),
+            batcher.resolve("dns.alice.near", "www", "AAAA"),
+            batcher.resolve("dns.alice.near", "www", "A"),
+        );
+        assert_eq!(a.unwrap().unwrap().name, "www/A");
+        assert_eq!(aaaa.unwrap().unwrap().name, "www/AAAA");
+        assert_eq!(a_again.unwrap().unwrap().name, "www/A");
+        assert_eq!(*batches.lock().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_full_batch_flushes_at_once() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        // A window long enough that only a full batch can finish in time
+        let batcher =
+            Batcher::with_window(counting_fetch(batches.clone()), Duration::from_secs(60));
+
+        let lookups = (0..MAX_BATCH).map(|i| {
+            let batcher = batcher.clone();
+            async move {
+                batcher
+                    .resolve("dns.alice.near", &format!("host{}", i), "A")
+                    .await
+            }
+        });
+        let results = tokio::time::timeout(Duration::from_secs(5), spawn_all(lookups))
+            .await
+            .unwrap();
+        assert!(results.iter().all(|r| r.is_ok()));
+        assert_eq!(*batches.lock().unwrap(), vec![MAX_BATCH]);
+    }
+
+    #[tokio::test]
+    async fn test_errors_reach_every_waiter() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let fetch: FetchFn = Arc::new(move |_, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Err(ResolverError::Unsupported("dns_resolve_many")) })
+        });
+        let batcher = Batcher::with_window(fetch, Duration::from_millis(20));
+
+        let (a, b) = tokio::join!(
+            batcher.resolve("dns.alice.near", "www", "A"),
+            batcher.resolve("dns.alice.near", "api", "A"),
+        );
+        assert!(matches!(a, Err(ResolverError::Unsupported(_))));
+        assert!(matches!(b, Err(ResolverError::Unsupported(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    async fn spawn_all<F>(futures: impl Iterator<Item = F>) -> Vec<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let handles: Vec<_> = futures.map(tokio::spawn).collect();
+        let mut outputs = Vec::with_capacity(handles.len());
+        for handle in handles {
+            outputs.push(handle.await.unwrap());
+        }
+        outputs
+    }
+}
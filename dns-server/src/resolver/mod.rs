@@ -1,2 +1,4 @@
+pub mod batch;
 pub mod near;
 pub mod upstream;
+pub mod verify;
@@ -1,14 +1,21 @@
+use near_api::types::account::ContractState;
 use near_api::{Account, Contract, NetworkConfig};
 use serde_json::json;
+use std::future::Future;
 use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio::task::JoinSet;
 use tracing::{debug, info, warn};
 use url::Url;
 
-use crate::cache::DnsCache;
-use crate::records::DnsRecord;
+use crate::cache::{ContractInfo, DnsCache};
+use crate::records::{DnsRecord, Resolution};
+use crate::resolver::batch::{BatchResult, Batcher, Query};
+use crate::resolver::verify::{Policy, VerifyConfig};
 
 /// Error type for NEAR resolution
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, Clone, thiserror::Error)]
 pub enum ResolverError {
     #[error("Invalid domain format")]
     InvalidDomain,
@@ -16,16 +23,166 @@ pub enum ResolverError {
     NotNearTld,
     #[error("Domain not found (NXDOMAIN)")]
     NotFound,
+    #[error("No records of this type (NODATA)")]
+    NoData,
+    #[error("Contract does not have {0}")]
+    Unsupported(&'static str),
     #[error("RPC error: {0}")]
     RpcError(String),
     #[error("Invalid account ID: {0}")]
     InvalidAccountId(String),
 }
 
+/// A NEAR zone and the contract serving it, e.g. `alice.near` and `dns.alice.near`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    pub name: String,
+    pub contract_id: String,
+}
+
+/// Whether an RPC error means the contract or method isn't there, rather than
+/// the call failing
+fn is_missing(err: &str) -> bool {
+    err.contains("MethodNotFound")
+        || err.contains("AccountDoesNotExist")
+        || err.contains("does not exist")
+        || err.contains("CodeDoesNotExist")
+}
+
+/// Result of one lookup in a batch
+type LookupResult = Result<Option<Resolution>, ResolverError>;
+
+/// Map a failed view call: a missing method means the contract doesn't
+/// support it, and a missing contract answers every query with NXDOMAIN
+fn view_error(method: &'static str, err: String, count: usize) -> BatchResult {
+    if err.contains("MethodNotFound") {
+        Err(ResolverError::Unsupported(method))
+    } else if is_missing(&err) {
+        Ok(vec![None; count])
+    } else {
+        Err(ResolverError::RpcError(err))
+    }
+}
+
+/// Look up one name with `dns_resolve`
+async fn fetch_resolution(
+    network: NetworkConfig,
+    contract: Contract,
+    query: Query,
+) -> LookupResult {
+    let (name, record_type) = query;
+    let args = json!({ "name": name, "record_type": record_type });
+    let result = contract
+        .call_function("dns_resolve", args)
+        .read_only()
+        .fetch_from(&network)
+        .await;
+    match result {
+        Ok(data) => Ok(data.data),
+        Err(e) => view_error("dns_resolve", e.to_string(), 1).map(|mut none| none.remove(0)),
+    }
+}
+
+/// Look up several names in one `dns_resolve_many` call
+async fn fetch_resolution_batch(
+    network: NetworkConfig,
+    contract: Contract,
+    queries: Vec<Query>,
+) -> BatchResult {
+    let count = queries.len();
+    let queries: Vec<_> = queries
+        .iter()
+        .map(|(name, record_type)| json!({ "name": name, "record_type": record_type }))
+        .collect();
+    let result = contract
+        .call_function("dns_resolve_many", json!({ "queries": queries }))
+        .read_only()
+        .fetch_from(&network)
+        .await;
+    match result {
+        Ok(data) => Ok(data.data),
+        Err(e) => view_error("dns_resolve_many", e.to_string(), count),
+    }
+}
+
+/// Run a batch of lookups against one contract: `dns_resolve` for a single
+/// query, `dns_resolve_many` for several
+async fn fetch_resolutions(
+    network: NetworkConfig,
+    cache: DnsCache,
+    contract_id: String,
+    queries: Vec<Query>,
+) -> BatchResult {
+    let account_id = near_api::AccountId::from_str(&contract_id)
+        .map_err(|_| ResolverError::InvalidAccountId(contract_id.clone()))?;
+    let contract = Contract(account_id);
+
+    let many = {
+        let (network, contract) = (network.clone(), contract.clone());
+        move |queries| fetch_resolution_batch(network, contract, queries)
+    };
+    let single = move |query| fetch_resolution(network.clone(), contract.clone(), query);
+    resolve_batch(&cache, &contract_id, queries, many, single).await
+}
+
+/// Run a batch of lookups with `many`, or with one `single` lookup per query
+/// when there is only one or the contract has no batch view
+///
+/// A contract found without the batch view is remembered, so later batches
+/// go straight to single lookups. Whether it has the single view is left to
+/// the caller, which sees [`ResolverError::Unsupported`] if it doesn't.
+async fn resolve_batch<M, MF, S, SF>(
+    cache: &DnsCache,
+    contract_id: &str,
+    queries: Vec<Query>,
+    many: M,
+    single: S,
+) -> BatchResult
+where
+    M: FnOnce(Vec<Query>) -> MF,
+    MF: Future<Output = BatchResult>,
+    S: Fn(Query) -> SF,
+    SF: Future<Output = LookupResult> + Send + 'static,
+{
+    if queries.len() > 1 && cache.get_batch_support(contract_id).await != Some(false) {
+        match many(queries.clone()).await {
+            Err(ResolverError::Unsupported(method)) => {
+                debug!(
+                    contract_id = %contract_id,
+                    method = %method,
+                    "Contract has no batch view, resolving queries one by one"
+                );
+                cache
+                    .insert_batch_support(contract_id.to_string(), false)
+                    .await;
+            }
+            result => return result,
+        }
+    }
+
+    let mut lookups = JoinSet::new();
+    for (i, query) in queries.into_iter().enumerate() {
+        let lookup = single(query);
+        lookups.spawn(async move { (i, lookup.await) });
+    }
+    let mut resolutions = vec![None; lookups.len()];
+    while let Some(joined) = lookups.join_next().await {
+        let (i, resolution) = joined.map_err(|e| ResolverError::RpcError(e.to_string()))?;
+        resolutions[i] = resolution?;
+    }
+    Ok(resolutions)
+}
+
 /// NEAR blockchain DNS resolver
 pub struct NearResolver {
     network: NetworkConfig,
     cache: DnsCache,
+    /// Groups `dns_resolve` lookups per contract
+    batcher: Batcher,
+    /// TLDs resolved through NEAR (lowercase)
+    tlds: RwLock<Vec<String>>,
+    /// Known DNS contract builds and what to do with other code
+    verify: RwLock<VerifyConfig>,
 }
 
 impl NearResolver {
@@ -36,13 +193,32 @@ impl NearResolver {
         })?;
         let network = NetworkConfig::from_rpc_url("custom", url);
 
-        Ok(Self { network, cache })
+        let tlds = Self::DEFAULT_NEAR_TLDS.iter().map(|tld| tld.to_string()).collect();
+
+        let batch_network = network.clone();
+        let batch_cache = cache.clone();
+        let batcher = Batcher::new(Arc::new(move |contract_id, queries| {
+            Box::pin(fetch_resolutions(
+                batch_network.clone(),
+                batch_cache.clone(),
+                contract_id,
+                queries,
+            ))
+        }));
+
+        Ok(Self {
+            network,
+            cache,
+            batcher,
+            tlds: RwLock::new(tlds),
+            verify: RwLock::new(VerifyConfig::default()),
+        })
     }
 
     /// Known NEAR TLDs (whitelist approach for safety)
     /// This prevents accidental resolution of traditional domains through NEAR
     /// even if those TLDs happen to exist as NEAR accounts (like "com" on testnet)
-    const KNOWN_NEAR_TLDS: &'static [&'static str] = &[
+    pub const DEFAULT_NEAR_TLDS: &'static [&'static str] = &[
         // Mainnet
         "near",
         // Testnet
@@ -55,37 +231,163 @@ impl NearResolver {
         "sharddog",
     ];
 
+    /// Replace the set of TLDs resolved through NEAR
+    pub fn set_tlds(&self, tlds: &[String]) {
+        let tlds = tlds.iter().map(|tld| tld.to_lowercase()).collect();
+        *self.tlds.write().expect("TLD lock poisoned") = tlds;
+    }
+
+    /// Replace the code hash allowlist and policies
+    ///
+    /// Cached contract checks are dropped when they change, since code hashes
+    /// are only looked up while some TLD has its contracts checked.
+    pub fn set_verify(&self, verify: &VerifyConfig) {
+        let mut current = self.verify.write().expect("verify lock poisoned");
+        if *current != *verify {
+            *current = verify.clone();
+            self.cache.clear_contracts();
+        }
+    }
+
+    /// The cache shared by this resolver
+    pub fn cache(&self) -> &DnsCache {
+        &self.cache
+    }
+
     /// Check if a TLD is a known NEAR TLD
     pub fn is_near_tld(&self, tld: &str) -> bool {
-        let is_near = Self::KNOWN_NEAR_TLDS.contains(&tld.to_lowercase().as_str());
+        let tld = tld.to_lowercase();
+        let is_near = self.tlds.read().expect("TLD lock poisoned").contains(&tld);
         debug!(tld = %tld, is_near = %is_near, "TLD check");
         is_near
     }
 
-    /// Check if a contract exists
+    /// Check if a zone contract exists and its code may be served
+    ///
+    /// The code hash is cached with the existence result; the allowlist is
+    /// applied on every check so a reloaded policy takes effect at once.
     async fn contract_exists(&self, contract_id: &str) -> bool {
         // Check cache first
-        if let Some(cached) = self.cache.get_contract(contract_id).await {
-            debug!(contract_id = %contract_id, cached = %cached, "Contract cache hit");
-            return cached;
+        let info = match self.cache.get_contract(contract_id).await {
+            Some(cached) => {
+                debug!(contract_id = %contract_id, exists = %cached.exists, "Contract cache hit");
+                cached
+            }
+            None => {
+                let info = self.fetch_contract(contract_id).await;
+                self.cache.insert_contract(contract_id.to_string(), info.clone()).await;
+                info
+            }
+        };
+
+        let allowed = self
+            .verify
+            .read()
+            .expect("verify lock poisoned")
+            .allows(contract_id, info.code_hash.as_deref());
+        if info.exists && !allowed {
+            debug!(contract_id = %contract_id, "Contract code is not a known DNS build");
         }
+        info.exists && allowed
+    }
+
+    /// Look up a zone contract account and the hash of its code
+    ///
+    /// Unknown code is logged here, once per cache lifetime, along with its
+    /// NEP-330 source metadata when the contract has any.
+    async fn fetch_contract(&self, contract_id: &str) -> ContractInfo {
+        let missing = ContractInfo {
+            exists: false,
+            code_hash: None,
+        };
+        let Ok(account_id) = near_api::AccountId::from_str(contract_id) else {
+            return missing;
+        };
+        let Ok(account) = Account(account_id.clone()).view().fetch_from(&self.network).await else {
+            debug!(contract_id = %contract_id, exists = false, "Contract existence check");
+            return missing;
+        };
 
-        let account_id = match near_api::AccountId::from_str(contract_id) {
-            Ok(id) => id,
-            Err(_) => {
-                self.cache.insert_contract(contract_id.to_string(), false).await;
-                return false;
+        // Not held across the RPC calls below
+        let verify = self.verify.read().expect("verify lock poisoned").clone();
+        let code_hash = match account.data.contract_state {
+            ContractState::None => {
+                debug!(contract_id = %contract_id, "Account has no contract deployed");
+                return missing;
+            }
+            ContractState::LocalHash(hash) | ContractState::GlobalHash(hash) => {
+                Some(hash.to_string())
+            }
+            // Only the publishing account is known; its code has to be fetched
+            // for the hash, so only do that when the hash matters
+            ContractState::GlobalAccountId(publisher) if verify.is_enabled() => {
+                match Contract::global_wasm()
+                    .by_account_id(publisher)
+                    .fetch_from(&self.network)
+                    .await
+                {
+                    Ok(code) => Some(code.data.hash.to_string()),
+                    Err(e) => {
+                        warn!(contract_id = %contract_id, error = %e, "Failed to fetch global contract code");
+                        None
+                    }
+                }
             }
+            ContractState::GlobalAccountId(_) => None,
         };
+        debug!(
+            contract_id = %contract_id,
+            exists = true,
+            code_hash = ?code_hash,
+            "Contract existence check"
+        );
 
-        // Check if account exists and has code deployed
-        // We check by trying to call a view function - if the contract doesn't have code,
-        // we'll get a specific error that we can distinguish
-        let exists = Account(account_id).view().fetch_from(&self.network).await.is_ok();
+        let policy = verify.policy_for(contract_id);
+        if policy != Policy::Allow && !verify.is_known(code_hash.as_deref()) {
+            self.log_unknown_code(account_id, code_hash.as_deref(), policy).await;
+        }
 
-        debug!(contract_id = %contract_id, exists = %exists, "Contract existence check");
-        self.cache.insert_contract(contract_id.to_string(), exists).await;
-        exists
+        ContractInfo {
+            exists: true,
+            code_hash,
+        }
+    }
+
+    /// Log a zone contract whose code isn't a known DNS build, with the
+    /// version and source link from its NEP-330 metadata
+    async fn log_unknown_code(
+        &self,
+        account_id: near_api::AccountId,
+        code_hash: Option<&str>,
+        policy: Policy,
+    ) {
+        let source = Contract(account_id.clone())
+            .contract_source_metadata()
+            .fetch_from(&self.network)
+            .await
+            .ok()
+            .map(|metadata| metadata.data);
+        let version = source.as_ref().and_then(|s| s.version.clone());
+        let link = source.as_ref().and_then(|s| s.link.clone());
+        let code_hash = code_hash.unwrap_or("unknown");
+
+        if policy == Policy::Reject {
+            warn!(
+                contract_id = %account_id,
+                code_hash = %code_hash,
+                version = ?version,
+                link = ?link,
+                "Rejecting zone contract with unknown code"
+            );
+        } else {
+            warn!(
+                contract_id = %account_id,
+                code_hash = %code_hash,
+                version = ?version,
+                link = ?link,
+                "Zone contract code is not a known DNS build"
+            );
+        }
     }
 
     /// Query DNS records from a specific contract
@@ -173,11 +475,7 @@ impl NearResolver {
                 );
 
                 // If it's a "method not found" or "account doesn't exist" error, return None
-                if err_str.contains("MethodNotFound")
-                    || err_str.contains("AccountDoesNotExist")
-                    || err_str.contains("does not exist")
-                    || err_str.contains("CodeDoesNotExist")
-                {
+                if is_missing(&err_str) {
                     Ok(None)
                 } else {
                     Err(ResolverError::RpcError(err_str))
@@ -186,6 +484,177 @@ impl NearResolver {
         }
     }
 
+    /// Resolve a name within one contract with its `dns_resolve` view
+    ///
+    /// Returns [`ResolverError::Unsupported`] for contracts without the view.
+    async fn query_resolve(
+        &self,
+        contract_id: &str,
+        dns_name: &str,
+        record_type: &str,
+    ) -> Result<Option<Resolution>, ResolverError> {
+        if let Some(cached) = self.cache.get_resolution(contract_id, dns_name, record_type).await {
+            debug!(
+                contract_id = %contract_id,
+                dns_name = %dns_name,
+                record_type = %record_type,
+                "Resolution cache hit"
+            );
+            return Ok(cached);
+        }
+
+        if !self.contract_exists(contract_id).await {
+            debug!(contract_id = %contract_id, "Contract does not exist");
+            return Ok(None);
+        }
+
+        match self.batcher.resolve(contract_id, dns_name, record_type).await {
+            Ok(resolution) => {
+                self.cache.insert_resolve_support(contract_id.to_string(), true).await;
+                self.cache
+                    .insert_resolution(
+                        contract_id.to_string(),
+                        dns_name.to_string(),
+                        record_type.to_string(),
+                        resolution.clone(),
+                    )
+                    .await;
+                Ok(resolution)
+            }
+            Err(ResolverError::Unsupported(method)) => {
+                debug!(contract_id = %contract_id, method = %method, "Contract has no resolve view");
+                self.cache.insert_resolve_support(contract_id.to_string(), false).await;
+                Err(ResolverError::Unsupported(method))
+            }
+            Err(e) => {
+                warn!(contract_id = %contract_id, error = %e, "Contract resolve failed");
+                Err(e)
+            }
+        }
+    }
+
+    /// Resolve a name within one contract
+    ///
+    /// Uses the contract's `dns_resolve` view when it has one. Older contracts
+    /// are asked for the name and then its wildcards one by one, in the order
+    /// given by `order` for this contract; they can't tell whether the name
+    /// exists with other types.
+    async fn resolve_in_contract(
+        &self,
+        contract_id: &str,
+        dns_name: &str,
+        record_type: &str,
+        order: &[(String, String)],
+    ) -> Result<Option<Resolution>, ResolverError> {
+        if self.cache.get_resolve_support(contract_id).await != Some(false) {
+            match self.query_resolve(contract_id, dns_name, record_type).await {
+                Err(ResolverError::Unsupported(_)) => {}
+                result => return result,
+            }
+        }
+
+        let candidates = order.iter().filter(|(contract, _)| contract == contract_id);
+        for (_, candidate) in candidates {
+            match self.query_contract(contract_id, candidate, record_type).await {
+                Ok(Some(records)) if !records.is_empty() => {
+                    return Ok(Some(Resolution {
+                        name: candidate.clone(),
+                        records,
+                        other_types: false,
+                    }));
+                }
+                Ok(_) => {
+                    debug!(
+                        contract_id = %contract_id,
+                        dns_name = %candidate,
+                        "No records found, trying next"
+                    );
+                }
+                Err(ResolverError::RpcError(e)) => {
+                    warn!(
+                        contract_id = %contract_id,
+                        error = %e,
+                        "RPC error, trying next"
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Fetch the zone serial of a contract
+    ///
+    /// Contracts from before zone serials report 0.
+    pub async fn zone_serial(&self, contract_id: &str) -> Result<u64, ResolverError> {
+        if let Some(serial) = self.cache.get_serial(contract_id).await {
+            debug!(contract_id = %contract_id, serial = serial, "Serial cache hit");
+            return Ok(serial);
+        }
+
+        let account_id = near_api::AccountId::from_str(contract_id)
+            .map_err(|_| ResolverError::InvalidAccountId(contract_id.to_string()))?;
+
+        let result = Contract(account_id)
+            .call_function("dns_zone_serial", json!({}))
+            .read_only()
+            .fetch_from(&self.network)
+            .await;
+
+        let serial = match result {
+            Ok(data) => {
+                // U64 is serialized as a JSON string
+                let serial: String = data.data;
+                serial.parse().map_err(|_| {
+                    ResolverError::RpcError(format!("Invalid zone serial '{}'", serial))
+                })?
+            }
+            Err(e) if is_missing(&e.to_string()) => {
+                debug!(contract_id = %contract_id, "Contract has no zone serial, using 0");
+                0
+            }
+            Err(e) => return Err(ResolverError::RpcError(e.to_string())),
+        };
+
+        self.cache.insert_serial(contract_id.to_string(), serial).await;
+        Ok(serial)
+    }
+
+    /// Find the zone a domain belongs to: the most specific `dns.<zone>`
+    /// contract account that exists
+    pub async fn find_zone(&self, domain: &str) -> Result<Option<Zone>, ResolverError> {
+        let domain = domain.trim_end_matches('.');
+        let parts: Vec<&str> = domain.split('.').collect();
+
+        if parts.len() < 2 {
+            return Err(ResolverError::InvalidDomain);
+        }
+        let (account_parts, tld) = parts.split_at(parts.len() - 1);
+        if !self.is_near_tld(tld[0]) {
+            return Err(ResolverError::NotNearTld);
+        }
+
+        for zone in Self::zone_candidates(account_parts, tld[0]) {
+            if self.contract_exists(&zone.contract_id).await {
+                return Ok(Some(zone));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Zones a name could belong to, most specific first
+    fn zone_candidates(parts: &[&str], tld: &str) -> Vec<Zone> {
+        (0..parts.len())
+            .map(|i| {
+                let name = format!("{}.{}", parts[i..].join("."), tld);
+                Zone {
+                    contract_id: format!("dns.{}", name),
+                    name,
+                }
+            })
+            .collect()
+    }
+
     /// Generate the resolution order for hierarchical lookup with wildcards
     fn resolution_order<'a>(&self, parts: &'a [&'a str], tld: &'a str) -> Vec<(String, String)> {
         let mut queries = vec![];
@@ -277,7 +746,7 @@ impl NearResolver {
             "Resolving NEAR domain"
         );
 
-        // Generate resolution order with wildcards
+        // Resolution order with wildcards, for contracts without dns_resolve
         let resolution_order = self.resolution_order(account_parts, tld);
 
         debug!(
@@ -285,47 +754,111 @@ impl NearResolver {
             "Resolution order"
         );
 
-        // Try each contract/name combination
-        for (contract_id, dns_name) in resolution_order {
-            match self.query_contract(&contract_id, &dns_name, record_type).await {
-                Ok(Some(records)) if !records.is_empty() => {
+        // Try each contract, most specific first. A contract that has the name
+        // without records of this type doesn't stop the search, but turns a
+        // miss into NODATA.
+        let mut name_exists = false;
+        for (i, zone) in Self::zone_candidates(account_parts, tld).into_iter().enumerate() {
+            // DNS name is the labels below the zone (or "@" at its root)
+            let dns_name = if i == 0 {
+                "@".to_string()
+            } else {
+                account_parts[..i].join(".")
+            };
+
+            // Cached answers can hold records that have expired since, and
+            // contracts only filter by the time of the block they are read at
+            let now = SystemTime::now();
+            let result = self
+                .resolve_in_contract(&zone.contract_id, &dns_name, record_type, &resolution_order)
+                .await
+                .map(|resolution| {
+                    resolution.map(|mut resolution| {
+                        resolution.records.retain(|r| r.is_active(now));
+                        resolution
+                    })
+                });
+
+            match result {
+                Ok(Some(resolution)) if !resolution.records.is_empty() => {
                     info!(
                         domain = %domain,
-                        contract_id = %contract_id,
-                        dns_name = %dns_name,
-                        count = records.len(),
+                        contract_id = %zone.contract_id,
+                        dns_name = %resolution.name,
+                        count = resolution.records.len(),
                         "Resolved via contract"
                     );
-                    return Ok(records);
+                    return Ok(resolution.records);
                 }
-                Ok(_) => {
+                Ok(Some(resolution)) => {
                     debug!(
-                        contract_id = %contract_id,
+                        contract_id = %zone.contract_id,
+                        dns_name = %resolution.name,
+                        other_types = resolution.other_types,
+                        "Name exists without records of this type, trying next"
+                    );
+                    name_exists = true;
+                }
+                Ok(None) => {
+                    debug!(
+                        contract_id = %zone.contract_id,
                         dns_name = %dns_name,
-                        "No records found, trying next"
+                        "Name not found, trying next"
                     );
-                    continue;
                 }
                 Err(ResolverError::RpcError(e)) => {
                     warn!(
-                        contract_id = %contract_id,
+                        contract_id = %zone.contract_id,
                         error = %e,
                         "RPC error, trying next"
                     );
-                    continue;
                 }
                 Err(e) => return Err(e),
             }
         }
 
+        if name_exists {
+            info!(domain = %domain, record_type = %record_type, "No records of this type (NODATA)");
+            return Err(ResolverError::NoData);
+        }
+
         info!(domain = %domain, "Domain not found (NXDOMAIN)");
         Err(ResolverError::NotFound)
     }
+
+    /// Resolve a name within one given contract, for zones outside the NEAR
+    /// TLDs such as reverse zones
+    ///
+    /// Errs with NODATA or NXDOMAIN like [`NearResolver::resolve`].
+    pub async fn resolve_in_zone(
+        &self,
+        contract_id: &str,
+        dns_name: &str,
+        record_type: &str,
+    ) -> Result<Vec<DnsRecord>, ResolverError> {
+        let order = [(contract_id.to_string(), dns_name.to_string())];
+        let now = SystemTime::now();
+        match self
+            .resolve_in_contract(contract_id, dns_name, record_type, &order)
+            .await?
+        {
+            Some(mut resolution) => {
+                resolution.records.retain(|r| r.is_active(now));
+                if resolution.records.is_empty() {
+                    Err(ResolverError::NoData)
+                } else {
+                    Ok(resolution.records)
+                }
+            }
+            None => Err(ResolverError::NotFound),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     fn create_test_resolver() -> NearResolver {
         let cache = DnsCache::new();
@@ -371,4 +904,72 @@ mod tests {
         assert!(order.contains(&("dns.frol.near".to_string(), "deep.sub".to_string())));
         assert!(order.contains(&("dns.frol.near".to_string(), "*".to_string())));
     }
+
+    #[test]
+    fn test_zone_candidates() {
+        let zones = NearResolver::zone_candidates(&["www", "frol"], "near");
+        assert_eq!(
+            zones,
+            vec![
+                Zone {
+                    name: "www.frol.near".to_string(),
+                    contract_id: "dns.www.frol.near".to_string(),
+                },
+                Zone {
+                    name: "frol.near".to_string(),
+                    contract_id: "dns.frol.near".to_string(),
+                },
+            ]
+        );
+    }
+
+    /// Single lookup answering with a resolution named after the query
+    async fn resolve_one((name, record_type): Query) -> LookupResult {
+        Ok(Some(Resolution {
+            name: format!("{}/{}", name, record_type),
+            records: Vec::new(),
+            other_types: false,
+        }))
+    }
+
+    fn queries(names: &[&str]) -> Vec<Query> {
+        names.iter().map(|name| (name.to_string(), "A".to_string())).collect()
+    }
+
+    #[tokio::test]
+    async fn test_batch_falls_back_to_single_lookups() {
+        let cache = DnsCache::new();
+        let batch_calls = AtomicUsize::new(0);
+        let many = |_| {
+            batch_calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(ResolverError::Unsupported("dns_resolve_many")) }
+        };
+
+        let batch = queries(&["www", "api"]);
+        let results = resolve_batch(&cache, "dns.alice.near", batch, many, resolve_one)
+            .await
+            .unwrap();
+        let names: Vec<_> = results.into_iter().map(|r| r.unwrap().name).collect();
+        assert_eq!(names, vec!["www/A", "api/A"]);
+
+        // Only batch support is cached as missing, and later batches skip it
+        assert_eq!(cache.get_batch_support("dns.alice.near").await, Some(false));
+        assert_eq!(cache.get_resolve_support("dns.alice.near").await, None);
+        let batch = queries(&["www", "mail"]);
+        resolve_batch(&cache, "dns.alice.near", batch, many, resolve_one)
+            .await
+            .unwrap();
+        assert_eq!(batch_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_without_single_view_is_unsupported() {
+        let cache = DnsCache::new();
+        let many = |_| async { Err(ResolverError::Unsupported("dns_resolve_many")) };
+        let single = |_| async { Err(ResolverError::Unsupported("dns_resolve")) };
+
+        let batch = queries(&["www", "api"]);
+        let result = resolve_batch(&cache, "dns.alice.near", batch, many, single).await;
+        assert!(matches!(result, Err(ResolverError::Unsupported("dns_resolve"))));
+    }
 }
@@ -1,6 +1,9 @@
-use hickory_resolver::config::ResolverConfig;
+use hickory_proto::xfer::Protocol;
+use hickory_resolver::config::{NameServerConfig, NameServerConfigGroup, ResolverConfig};
 use hickory_resolver::TokioResolver;
+use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::RwLock;
 use tracing::{debug, info};
 
 use hickory_proto::rr::{Name, RData, Record, RecordType};
@@ -16,17 +19,47 @@ pub enum UpstreamError {
 
 /// Upstream DNS resolver for non-NEAR domains
 pub struct UpstreamResolver {
-    resolver: TokioResolver,
+    resolver: RwLock<TokioResolver>,
 }
 
 impl UpstreamResolver {
     /// Create a new upstream resolver with default DNS servers (Google, Cloudflare)
     pub fn new() -> Self {
-        let resolver = TokioResolver::builder_with_config(
-            ResolverConfig::default(),
+        Self::with_nameservers(&[])
+    }
+
+    /// Create a new upstream resolver using the given nameservers
+    ///
+    /// An empty list falls back to the default DNS servers.
+    pub fn with_nameservers(nameservers: &[SocketAddr]) -> Self {
+        Self {
+            resolver: RwLock::new(Self::build(nameservers)),
+        }
+    }
+
+    /// Switch to a new set of nameservers
+    ///
+    /// Lookups already in flight finish on the previous resolver.
+    pub fn set_nameservers(&self, nameservers: &[SocketAddr]) {
+        *self.resolver.write().expect("resolver lock poisoned") = Self::build(nameservers);
+    }
+
+    fn build(nameservers: &[SocketAddr]) -> TokioResolver {
+        let config = if nameservers.is_empty() {
+            ResolverConfig::default()
+        } else {
+            let mut group = NameServerConfigGroup::with_capacity(nameservers.len() * 2);
+            for addr in nameservers {
+                group.push(NameServerConfig::new(*addr, Protocol::Udp));
+                group.push(NameServerConfig::new(*addr, Protocol::Tcp));
+            }
+            ResolverConfig::from_parts(None, vec![], group)
+        };
+
+        TokioResolver::builder_with_config(
+            config,
             hickory_resolver::name_server::TokioConnectionProvider::default(),
-        ).build();
-        Self { resolver }
+        ).build()
     }
 
     /// Resolve a domain using upstream DNS
@@ -40,10 +73,11 @@ impl UpstreamResolver {
 
         debug!(domain = %domain, record_type = ?record_type, "Resolving via upstream DNS");
 
+        let resolver = self.resolver.read().expect("resolver lock poisoned").clone();
+
         match record_type {
             RecordType::A => {
-                let response = self
-                    .resolver
+                let response = resolver
                     .ipv4_lookup(domain)
                     .await
                     .map_err(|e| UpstreamError::ResolutionFailed(e.to_string()))?;
@@ -67,8 +101,7 @@ impl UpstreamResolver {
                 }
             }
             RecordType::AAAA => {
-                let response = self
-                    .resolver
+                let response = resolver
                     .ipv6_lookup(domain)
                     .await
                     .map_err(|e| UpstreamError::ResolutionFailed(e.to_string()))?;
@@ -92,8 +125,7 @@ impl UpstreamResolver {
                 }
             }
             RecordType::MX => {
-                let response = self
-                    .resolver
+                let response = resolver
                     .mx_lookup(domain)
                     .await
                     .map_err(|e| UpstreamError::ResolutionFailed(e.to_string()))?;
@@ -120,8 +152,7 @@ impl UpstreamResolver {
                 }
             }
             RecordType::TXT => {
-                let response = self
-                    .resolver
+                let response = resolver
                     .txt_lookup(domain)
                     .await
                     .map_err(|e| UpstreamError::ResolutionFailed(e.to_string()))?;
@@ -150,8 +181,7 @@ impl UpstreamResolver {
                 }
             }
             RecordType::NS => {
-                let response = self
-                    .resolver
+                let response = resolver
                     .ns_lookup(domain)
                     .await
                     .map_err(|e| UpstreamError::ResolutionFailed(e.to_string()))?;
@@ -175,8 +205,7 @@ impl UpstreamResolver {
                 }
             }
             RecordType::SOA => {
-                let response = self
-                    .resolver
+                let response = resolver
                     .soa_lookup(domain)
                     .await
                     .map_err(|e| UpstreamError::ResolutionFailed(e.to_string()))?;
@@ -197,8 +226,7 @@ impl UpstreamResolver {
             }
             _ => {
                 // For other record types, use generic lookup
-                let response = self
-                    .resolver
+                let response = resolver
                     .lookup(domain, record_type)
                     .await
                     .map_err(|e| UpstreamError::ResolutionFailed(e.to_string()))?;
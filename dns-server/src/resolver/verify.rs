@@ -0,0 +1,135 @@
+use near_api::types::CryptoHash;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// What to do with a zone contract whose code isn't a known DNS build
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    /// Treat the zone as if it had no contract
+    Reject,
+    /// Serve it, but log the unknown code
+    Warn,
+    /// Serve it without checking the code
+    #[default]
+    Allow,
+}
+
+/// Known DNS contract builds, checked against the code of each zone contract
+///
+/// List the code hashes of reproducible builds (`cargo near build
+/// reproducible-wasm`): their NEP-330 metadata points at the exact source, so
+/// anyone can rebuild them and compare the hash. Contracts with other code
+/// are handled by the policy of their TLD, or `policy` for TLDs not listed.
+/// Their NEP-330 metadata, when they have it, is logged to help tell which
+/// build they run.
+///
+/// ```toml
+/// [verify]
+/// code_hashes = ["9dV2RBQ6xRcnjhpMCvA8pGv9RdG2rP3YwsmCUXkmFk4p"]
+/// policy = "warn"
+///
+/// [verify.tlds]
+/// near = "reject"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VerifyConfig {
+    /// Base58 code hashes of known DNS contract builds
+    pub code_hashes: Vec<String>,
+    /// Policy for TLDs not listed in `tlds` (default: allow)
+    pub policy: Policy,
+    /// Policy per TLD
+    pub tlds: HashMap<String, Policy>,
+}
+
+impl VerifyConfig {
+    /// Check that every listed code hash is a base58 32-byte hash
+    pub fn validate(&self) -> Result<(), String> {
+        for hash in &self.code_hashes {
+            CryptoHash::from_str(hash).map_err(|_| format!("'{}' is not a code hash", hash))?;
+        }
+        Ok(())
+    }
+
+    /// Whether any TLD has its contracts' code checked
+    pub fn is_enabled(&self) -> bool {
+        self.policy != Policy::Allow || self.tlds.values().any(|p| *p != Policy::Allow)
+    }
+
+    /// Policy for the zone contract `contract_id`, by its TLD
+    pub fn policy_for(&self, contract_id: &str) -> Policy {
+        let tld = contract_id.rsplit('.').next().unwrap_or(contract_id);
+        self.tlds
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(tld))
+            .map_or(self.policy, |(_, policy)| *policy)
+    }
+
+    /// Whether `code_hash` is a known DNS build; an unknown hash never is
+    pub fn is_known(&self, code_hash: Option<&str>) -> bool {
+        code_hash.is_some_and(|hash| self.code_hashes.iter().any(|known| known == hash))
+    }
+
+    /// Whether the zone contract `contract_id` with code `code_hash` may be served
+    pub fn allows(&self, contract_id: &str, code_hash: Option<&str>) -> bool {
+        self.policy_for(contract_id) != Policy::Reject || self.is_known(code_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KNOWN: &str = "9dV2RBQ6xRcnjhpMCvA8pGv9RdG2rP3YwsmCUXkmFk4p";
+    const OTHER: &str = "11111111111111111111111111111111";
+
+    fn config() -> VerifyConfig {
+        toml::from_str(&format!(
+            r#"
+            code_hashes = ["{KNOWN}"]
+            policy = "warn"
+
+            [tlds]
+            near = "reject"
+            testnet = "allow"
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_policy_by_tld() {
+        let config = config();
+        assert!(config.validate().is_ok());
+        assert!(config.is_enabled());
+        assert_eq!(config.policy_for("dns.alice.near"), Policy::Reject);
+        assert_eq!(config.policy_for("dns.alice.NEAR"), Policy::Reject);
+        assert_eq!(config.policy_for("dns.alice.testnet"), Policy::Allow);
+        assert_eq!(config.policy_for("dns.alice.tg"), Policy::Warn);
+    }
+
+    #[test]
+    fn test_allows_known_code_only_when_rejecting() {
+        let config = config();
+        assert!(config.allows("dns.alice.near", Some(KNOWN)));
+        assert!(!config.allows("dns.alice.near", Some(OTHER)));
+        assert!(!config.allows("dns.alice.near", None));
+        assert!(config.allows("dns.alice.tg", Some(OTHER)));
+        assert!(config.allows("dns.alice.testnet", None));
+    }
+
+    #[test]
+    fn test_default_allows_everything() {
+        let config = VerifyConfig::default();
+        assert!(!config.is_enabled());
+        assert!(config.allows("dns.alice.near", None));
+
+        let bad = VerifyConfig {
+            code_hashes: vec!["not-a-hash".to_string()],
+            ..Default::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+}
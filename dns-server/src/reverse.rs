@@ -0,0 +1,194 @@
+use hickory_proto::rr::Name;
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Reverse zones (`in-addr.arpa` and `ip6.arpa`) served from NEAR
+///
+/// Each zone covers an address prefix, which has to end on an octet boundary
+/// for IPv4 and a nibble boundary for IPv6 so it maps to a reverse zone name.
+/// PTR records come from the zone's `contract`, stored under names relative
+/// to the zone (`7` for `203.0.113.7` in `113.0.203.in-addr.arpa`). Addresses
+/// without one get a PTR synthesized from the first of `names` whose A or AAAA
+/// records, read from its own contract, include the address, so forward and
+/// reverse lookups agree. Reverse names outside every zone go upstream.
+///
+/// ```toml
+/// [[reverse.zones]]
+/// prefix = "203.0.113.0/24"
+/// contract = "dns.reverse.infra.near"
+/// names = ["web.infra.near", "mail.infra.near"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReverseConfig {
+    /// Reverse zones, by address prefix
+    pub zones: Vec<ReverseZone>,
+}
+
+/// One reverse zone and where its PTR records come from
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReverseZone {
+    /// Addresses the zone covers
+    pub prefix: IpNet,
+    /// NEAR contract holding the zone's records
+    #[serde(default)]
+    pub contract: Option<String>,
+    /// NEAR names whose addresses get a synthesized PTR record
+    #[serde(default)]
+    pub names: Vec<String>,
+}
+
+impl ReverseConfig {
+    /// Check that every zone maps to a reverse zone name and has a source of records
+    pub fn validate(&self) -> Result<(), String> {
+        for zone in &self.zones {
+            let prefix = zone.prefix;
+            let step = match prefix {
+                IpNet::V4(_) => 8,
+                IpNet::V6(_) => 4,
+            };
+            if prefix.prefix_len() % step != 0 {
+                return Err(format!("{} doesn't end on a {}-bit boundary", prefix, step));
+            }
+            if prefix.trunc() != prefix {
+                return Err(format!(
+                    "{} has host bits set; use {}",
+                    prefix,
+                    prefix.trunc()
+                ));
+            }
+            if zone.contract.is_none() && zone.names.is_empty() {
+                return Err(format!("{} needs a contract or names", prefix));
+            }
+            if let Some(name) = zone.names.iter().find(|name| Name::from_str(name).is_err()) {
+                return Err(format!("'{}' is not a valid name", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// The zone serving `name`, the most specific one if several do
+    pub fn zone_for(&self, name: &Name) -> Option<&ReverseZone> {
+        let net = name.parse_arpa_name().ok()?;
+        self.zones
+            .iter()
+            .filter(|zone| zone.prefix.contains(&net))
+            .max_by_key(|zone| zone.prefix.prefix_len())
+    }
+}
+
+impl ReverseZone {
+    /// Name of the reverse zone, e.g. `113.0.203.in-addr.arpa.`
+    pub fn origin(&self) -> Name {
+        let (labels, per_label) = match self.prefix {
+            IpNet::V4(_) => (4, 8),
+            IpNet::V6(_) => (32, 4),
+        };
+        let full = Name::from(self.prefix.network());
+        let kept = self.prefix.prefix_len() / per_label;
+        // Drop the labels below the prefix, keeping `in-addr.arpa`/`ip6.arpa`
+        full.trim_to(full.num_labels() as usize - (labels - kept as usize))
+    }
+
+    /// Name of `name` within the zone, as stored in the contract (`@` at the apex)
+    pub fn relative_name(&self, name: &Name) -> String {
+        let below = name.num_labels().saturating_sub(self.origin().num_labels());
+        if below == 0 {
+            return "@".to_string();
+        }
+        name.iter()
+            .take(below as usize)
+            .map(|label| String::from_utf8_lossy(label).to_ascii_lowercase())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// The address a full reverse name stands for, e.g. `203.0.113.7` for
+/// `7.113.0.203.in-addr.arpa.`
+pub fn address_of(name: &Name) -> Option<IpAddr> {
+    let net = name.parse_arpa_name().ok()?;
+    (net.prefix_len() == net.max_prefix_len()).then(|| net.addr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ReverseConfig {
+        toml::from_str(
+            r#"
+            [[zones]]
+            prefix = "203.0.0.0/16"
+            contract = "dns.reverse.infra.near"
+
+            [[zones]]
+            prefix = "203.0.113.0/24"
+            names = ["web.infra.near"]
+
+            [[zones]]
+            prefix = "2001:db8::/32"
+            contract = "dns.reverse6.infra.near"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn name(name: &str) -> Name {
+        Name::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_zone_for_picks_most_specific() {
+        let config = config();
+        assert!(config.validate().is_ok());
+
+        let zone = config.zone_for(&name("7.113.0.203.in-addr.arpa.")).unwrap();
+        assert_eq!(zone.prefix, "203.0.113.0/24".parse::<IpNet>().unwrap());
+        assert_eq!(zone.origin(), name("113.0.203.in-addr.arpa."));
+
+        let zone = config.zone_for(&name("9.5.0.203.in-addr.arpa.")).unwrap();
+        assert_eq!(zone.origin(), name("0.203.in-addr.arpa."));
+        assert_eq!(zone.relative_name(&name("9.5.0.203.in-addr.arpa.")), "9.5");
+        assert_eq!(zone.relative_name(&name("0.203.in-addr.arpa.")), "@");
+
+        assert!(config.zone_for(&name("1.2.0.198.in-addr.arpa.")).is_none());
+        assert!(config.zone_for(&name("203.in-addr.arpa.")).is_none());
+        assert!(config.zone_for(&name("example.near.")).is_none());
+    }
+
+    #[test]
+    fn test_ipv6_zone() {
+        let config = config();
+        let addr: IpAddr = "2001:db8::1".parse().unwrap();
+        let reverse = Name::from(addr);
+
+        let zone = config.zone_for(&reverse).unwrap();
+        assert_eq!(zone.origin(), name("8.b.d.0.1.0.0.2.ip6.arpa."));
+        assert_eq!(zone.relative_name(&reverse).split('.').count(), 24);
+        assert_eq!(address_of(&reverse), Some(addr));
+        assert_eq!(address_of(&zone.origin()), None);
+    }
+
+    #[test]
+    fn test_rejects_unaligned_prefixes() {
+        let zone = |prefix: &str| ReverseConfig {
+            zones: vec![ReverseZone {
+                prefix: prefix.parse().unwrap(),
+                contract: Some("dns.reverse.near".to_string()),
+                names: Vec::new(),
+            }],
+        };
+        assert!(zone("203.0.113.0/25").validate().is_err());
+        assert!(zone("203.0.113.7/24").validate().is_err());
+        assert!(zone("2001:db8::/34").validate().is_err());
+        assert!(zone("2001:db8::/36").validate().is_ok());
+
+        let mut empty = zone("203.0.113.0/24");
+        empty.zones[0].contract = None;
+        assert!(empty.validate().is_err());
+    }
+}
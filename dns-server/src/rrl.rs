@@ -0,0 +1,532 @@
+use async_trait::async_trait;
+use hickory_proto::op::{Header, ResponseCode};
+use hickory_proto::rr::{LowerName, Record, RecordType};
+use hickory_proto::xfer::Protocol;
+use hickory_server::authority::{MessageResponse, MessageResponseBuilder};
+use hickory_server::server::{Request, RequestHandler, ResponseHandler, ResponseInfo};
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Response Rate Limiting settings
+///
+/// Modelled on BIND's RRL: each client prefix gets a token bucket per response
+/// class. Once a bucket runs dry, every `slip`-th limited response is sent
+/// truncated (TC=1, no records) so real clients can retry over TCP, and the
+/// rest are dropped. Only UDP is limited; TCP can't be spoofed.
+///
+/// ```toml
+/// [rrl]
+/// enabled = true
+/// responses_per_second = 10
+/// slip = 2
+/// exempt = ["10.0.0.0/8"]
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RrlConfig {
+    /// Turn rate limiting on
+    pub enabled: bool,
+    /// Identical answers (same name and type) per second per client prefix; 0 = unlimited
+    pub responses_per_second: u32,
+    /// NXDOMAIN responses per second per client prefix; 0 = unlimited
+    pub nxdomains_per_second: u32,
+    /// Error responses (SERVFAIL, REFUSED, ...) per second per client prefix; 0 = unlimited
+    pub errors_per_second: u32,
+    /// Seconds of over-limit traffic a bucket remembers; a flood has to stop
+    /// for about this long before the prefix is served again
+    pub window_secs: u64,
+    /// Send every Nth limited response truncated instead of dropping it (0 = always drop)
+    pub slip: u32,
+    /// IPv4 clients sharing this prefix share buckets
+    pub ipv4_prefix_len: u8,
+    /// IPv6 clients sharing this prefix share buckets
+    pub ipv6_prefix_len: u8,
+    /// Clients that are never limited
+    pub exempt: Vec<IpNet>,
+    /// Maximum number of buckets tracked at once
+    pub max_entries: usize,
+}
+
+impl Default for RrlConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            responses_per_second: 5,
+            nxdomains_per_second: 5,
+            errors_per_second: 5,
+            window_secs: 15,
+            slip: 2,
+            ipv4_prefix_len: 24,
+            ipv6_prefix_len: 56,
+            exempt: vec![
+                "127.0.0.0/8".parse().expect("valid prefix"),
+                "::1/128".parse().expect("valid prefix"),
+            ],
+            max_entries: 100_000,
+        }
+    }
+}
+
+/// Response classes with separate buckets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResponseKind {
+    Answer,
+    NxDomain,
+    Error,
+}
+
+impl From<ResponseCode> for ResponseKind {
+    fn from(code: ResponseCode) -> Self {
+        match code {
+            ResponseCode::NoError => ResponseKind::Answer,
+            ResponseCode::NXDomain => ResponseKind::NxDomain,
+            _ => ResponseKind::Error,
+        }
+    }
+}
+
+/// What to do with a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Send,
+    Slip,
+    Drop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BucketKey {
+    prefix: IpNet,
+    kind: ResponseKind,
+    /// Answers are limited per name and type, other classes per prefix only
+    query: Option<(LowerName, RecordType)>,
+}
+
+struct Bucket {
+    /// Available responses; negative while the client is over its limit
+    balance: f64,
+    last_seen: Instant,
+    /// Limited responses so far, for the slip ratio
+    limited: u64,
+}
+
+/// Counters exposed for monitoring
+#[derive(Debug, Default)]
+pub struct RrlMetrics {
+    /// Responses checked against a bucket
+    pub checked: AtomicU64,
+    /// Responses from exempt clients
+    pub exempt: AtomicU64,
+    /// Responses sent truncated instead of in full
+    pub slipped: AtomicU64,
+    /// Responses dropped
+    pub dropped: AtomicU64,
+}
+
+impl RrlMetrics {
+    /// Current values as `(checked, exempt, slipped, dropped)`
+    pub fn snapshot(&self) -> (u64, u64, u64, u64) {
+        (
+            self.checked.load(Ordering::Relaxed),
+            self.exempt.load(Ordering::Relaxed),
+            self.slipped.load(Ordering::Relaxed),
+            self.dropped.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Token-bucket response rate limiter
+pub struct RateLimiter {
+    config: RwLock<RrlConfig>,
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+    metrics: RrlMetrics,
+}
+
+impl RateLimiter {
+    pub fn new(config: RrlConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            buckets: Mutex::new(HashMap::new()),
+            metrics: RrlMetrics::default(),
+        }
+    }
+
+    /// Apply new settings; bucket state is kept unless the prefix lengths changed
+    pub fn reconfigure(&self, config: RrlConfig) {
+        let mut current = self.config.write().expect("RRL lock poisoned");
+        if current.ipv4_prefix_len != config.ipv4_prefix_len
+            || current.ipv6_prefix_len != config.ipv6_prefix_len
+        {
+            self.buckets.lock().expect("RRL lock poisoned").clear();
+        }
+        *current = config;
+    }
+
+    pub fn metrics(&self) -> &RrlMetrics {
+        &self.metrics
+    }
+
+    /// Whether responses to `client` go through the limiter at all
+    fn applies_to(&self, client: IpAddr) -> bool {
+        let config = self.config.read().expect("RRL lock poisoned");
+        if !config.enabled {
+            return false;
+        }
+        let client = client.to_canonical();
+        if config.exempt.iter().any(|net| net.contains(&client)) {
+            self.metrics.exempt.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Decide what to do with a response to `client`
+    pub fn check(
+        &self,
+        client: IpAddr,
+        kind: ResponseKind,
+        query: Option<(LowerName, RecordType)>,
+        now: Instant,
+    ) -> Verdict {
+        let config = self.config.read().expect("RRL lock poisoned");
+        let rate = match kind {
+            ResponseKind::Answer => config.responses_per_second,
+            ResponseKind::NxDomain => config.nxdomains_per_second,
+            ResponseKind::Error => config.errors_per_second,
+        };
+        if rate == 0 {
+            return Verdict::Send;
+        }
+        self.metrics.checked.fetch_add(1, Ordering::Relaxed);
+
+        let client = client.to_canonical();
+        let prefix_len = match client {
+            IpAddr::V4(_) => config.ipv4_prefix_len.min(32),
+            IpAddr::V6(_) => config.ipv6_prefix_len.min(128),
+        };
+        let prefix = IpNet::new(client, prefix_len)
+            .expect("prefix length is clamped")
+            .trunc();
+        let key = BucketKey {
+            prefix,
+            kind,
+            query: if kind == ResponseKind::Answer {
+                query
+            } else {
+                None
+            },
+        };
+
+        let rate = f64::from(rate);
+        let window = config.window_secs as f64;
+        let mut buckets = self.buckets.lock().expect("RRL lock poisoned");
+        if buckets.len() >= config.max_entries && !buckets.contains_key(&key) {
+            Self::evict(&mut buckets, now, Duration::from_secs(config.window_secs));
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            balance: rate,
+            last_seen: now,
+            limited: 0,
+        });
+
+        // Refill for the time since the last response, capped at one second's worth
+        let elapsed = now
+            .saturating_duration_since(bucket.last_seen)
+            .as_secs_f64();
+        bucket.balance = (bucket.balance + elapsed * rate).min(rate) - 1.0;
+        bucket.balance = bucket.balance.max(-rate * window);
+        bucket.last_seen = now;
+
+        if bucket.balance >= 0.0 {
+            return Verdict::Send;
+        }
+
+        bucket.limited += 1;
+        if config.slip > 0 && bucket.limited % u64::from(config.slip) == 0 {
+            self.metrics.slipped.fetch_add(1, Ordering::Relaxed);
+            Verdict::Slip
+        } else {
+            self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+            Verdict::Drop
+        }
+    }
+
+    /// Make room in a full table by forgetting buckets idle for a whole window
+    fn evict(buckets: &mut HashMap<BucketKey, Bucket>, now: Instant, window: Duration) {
+        let before = buckets.len();
+        buckets.retain(|_, bucket| now.saturating_duration_since(bucket.last_seen) < window);
+        if buckets.len() == before {
+            warn!(
+                entries = before,
+                "RRL table full of active buckets, resetting it"
+            );
+            buckets.clear();
+        }
+    }
+}
+
+/// Request handler wrapper that applies response rate limiting to UDP queries
+pub struct RateLimitedHandler<H> {
+    inner: H,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<H> RateLimitedHandler<H> {
+    pub fn new(inner: H, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait]
+impl<H: RequestHandler> RequestHandler for RateLimitedHandler<H> {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handle: R,
+    ) -> ResponseInfo {
+        let client = request.src().ip();
+        if request.protocol() != Protocol::Udp || !self.limiter.applies_to(client) {
+            return self.inner.handle_request(request, response_handle).await;
+        }
+
+        let query = request
+            .queries()
+            .first()
+            .map(|q| (q.name().clone(), q.query_type()));
+        let slip = Arc::new(Mutex::new(None));
+        let limited = LimitedResponseHandle {
+            inner: response_handle.clone(),
+            limiter: self.limiter.clone(),
+            client,
+            query,
+            slip: slip.clone(),
+        };
+
+        let info = self.inner.handle_request(request, limited).await;
+
+        // The response was withheld; answer with an empty truncated one instead
+        let slip_header = slip.lock().expect("slip lock poisoned").take();
+        if let Some(header) = slip_header {
+            let response =
+                MessageResponseBuilder::from_message_request(request).build_no_records(header);
+            if let Err(e) = response_handle.send_response(response).await {
+                debug!(error = %e, "Failed to send truncated response");
+            }
+        }
+        info
+    }
+}
+
+/// Response handle that checks each response against the rate limiter
+#[derive(Clone)]
+struct LimitedResponseHandle<R> {
+    inner: R,
+    limiter: Arc<RateLimiter>,
+    client: IpAddr,
+    query: Option<(LowerName, RecordType)>,
+    /// Header for a truncated reply, set when the response is slipped
+    slip: Arc<Mutex<Option<Header>>>,
+}
+
+#[async_trait]
+impl<R: ResponseHandler> ResponseHandler for LimitedResponseHandle<R> {
+    async fn send_response<'a>(
+        &mut self,
+        response: MessageResponse<
+            '_,
+            'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+            impl Iterator<Item = &'a Record> + Send + 'a,
+        >,
+    ) -> io::Result<ResponseInfo> {
+        let header = *response.header();
+        let kind = ResponseKind::from(header.response_code());
+
+        match self
+            .limiter
+            .check(self.client, kind, self.query.clone(), Instant::now())
+        {
+            Verdict::Send => self.inner.send_response(response).await,
+            Verdict::Slip => {
+                debug!(client = %self.client, kind = ?kind, "RRL: sending truncated response");
+                let mut truncated = header;
+                truncated.set_truncated(true);
+                *self.slip.lock().expect("slip lock poisoned") = Some(truncated);
+                Ok(truncated.into())
+            }
+            Verdict::Drop => {
+                debug!(client = %self.client, kind = ?kind, "RRL: dropping response");
+                Ok(header.into())
+            }
+        }
+    }
+}
+
+/// Periodically log RRL counters while anything is being limited
+pub async fn log_metrics(limiter: Arc<RateLimiter>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    let mut last = limiter.metrics().snapshot();
+    loop {
+        ticker.tick().await;
+        let current = limiter.metrics().snapshot();
+        let (checked, exempt, slipped, dropped) = current;
+        if (slipped, dropped) != (last.2, last.3) {
+            info!(
+                checked = checked - last.0,
+                exempt = exempt - last.1,
+                slipped = slipped - last.2,
+                dropped = dropped - last.3,
+                "RRL limited responses"
+            );
+        }
+        last = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn limiter(rate: u32, slip: u32) -> RateLimiter {
+        RateLimiter::new(RrlConfig {
+            enabled: true,
+            responses_per_second: rate,
+            nxdomains_per_second: rate,
+            errors_per_second: rate,
+            slip,
+            ..Default::default()
+        })
+    }
+
+    fn query(name: &str) -> Option<(LowerName, RecordType)> {
+        Some((LowerName::from_str(name).unwrap(), RecordType::A))
+    }
+
+    #[test]
+    fn test_limits_after_rate_exhausted() {
+        let rrl = limiter(3, 0);
+        let client: IpAddr = "203.0.113.5".parse().unwrap();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(
+                rrl.check(client, ResponseKind::Answer, query("a.near."), now),
+                Verdict::Send
+            );
+        }
+        assert_eq!(
+            rrl.check(client, ResponseKind::Answer, query("a.near."), now),
+            Verdict::Drop
+        );
+
+        // Same prefix, different name: separate bucket
+        let neighbour: IpAddr = "203.0.113.77".parse().unwrap();
+        assert_eq!(
+            rrl.check(neighbour, ResponseKind::Answer, query("b.near."), now),
+            Verdict::Send
+        );
+        // Same prefix, same name: shared bucket
+        assert_eq!(
+            rrl.check(neighbour, ResponseKind::Answer, query("a.near."), now),
+            Verdict::Drop
+        );
+    }
+
+    #[test]
+    fn test_refills_over_time() {
+        let rrl = limiter(2, 0);
+        let client: IpAddr = "198.51.100.1".parse().unwrap();
+        let start = Instant::now();
+
+        for _ in 0..2 {
+            assert_eq!(
+                rrl.check(client, ResponseKind::NxDomain, None, start),
+                Verdict::Send
+            );
+        }
+        assert_eq!(
+            rrl.check(client, ResponseKind::NxDomain, None, start),
+            Verdict::Drop
+        );
+
+        // The debt (-1) plus one more second of credit brings the balance back up
+        let later = start + Duration::from_secs(2);
+        assert_eq!(
+            rrl.check(client, ResponseKind::NxDomain, None, later),
+            Verdict::Send
+        );
+    }
+
+    #[test]
+    fn test_slip_ratio() {
+        let rrl = limiter(1, 2);
+        let client: IpAddr = "2001:db8::1".parse().unwrap();
+        let now = Instant::now();
+
+        assert_eq!(
+            rrl.check(client, ResponseKind::Error, None, now),
+            Verdict::Send
+        );
+        let verdicts: Vec<_> = (0..4)
+            .map(|_| rrl.check(client, ResponseKind::Error, None, now))
+            .collect();
+        assert_eq!(
+            verdicts,
+            vec![Verdict::Drop, Verdict::Slip, Verdict::Drop, Verdict::Slip]
+        );
+
+        let (checked, _, slipped, dropped) = rrl.metrics().snapshot();
+        assert_eq!((checked, slipped, dropped), (5, 2, 2));
+    }
+
+    #[test]
+    fn test_exempt_and_disabled() {
+        let rrl = limiter(1, 0);
+        assert!(!rrl.applies_to("127.0.0.1".parse().unwrap()));
+        assert!(rrl.applies_to("192.0.2.1".parse().unwrap()));
+
+        rrl.reconfigure(RrlConfig::default());
+        assert!(!rrl.applies_to("192.0.2.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_evicts_idle_buckets() {
+        let rrl = RateLimiter::new(RrlConfig {
+            enabled: true,
+            max_entries: 2,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        rrl.check(
+            "192.0.2.1".parse().unwrap(),
+            ResponseKind::Error,
+            None,
+            start,
+        );
+        rrl.check(
+            "198.51.100.1".parse().unwrap(),
+            ResponseKind::Error,
+            None,
+            start,
+        );
+
+        let later = start + Duration::from_secs(60);
+        rrl.check(
+            "203.0.113.1".parse().unwrap(),
+            ResponseKind::Error,
+            None,
+            later,
+        );
+        assert_eq!(rrl.buckets.lock().unwrap().len(), 1);
+    }
+}
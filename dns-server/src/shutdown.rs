@@ -0,0 +1,219 @@
+use async_trait::async_trait;
+use hickory_proto::op::{Header, ResponseCode};
+use hickory_server::authority::MessageResponseBuilder;
+use hickory_server::server::{Request, RequestHandler, ResponseHandler, ResponseInfo};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+/// Shared drain state between the request handler and the shutdown logic
+#[derive(Default)]
+pub struct DrainState {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+impl DrainState {
+    /// Number of requests currently being handled
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Stop accepting new requests and wait for in-flight ones to finish
+    ///
+    /// Returns `false` if requests were still running when `deadline` passed.
+    /// Nothing needs flushing afterwards: the server keeps no persistent
+    /// state, only caches in memory that are rebuilt from NEAR on start.
+    pub async fn drain(&self, deadline: Duration) -> bool {
+        self.draining.store(true, Ordering::Release);
+        info!(in_flight = self.in_flight(), "Draining in-flight queries");
+
+        let wait_idle = async {
+            loop {
+                // Register interest before checking so a wakeup isn't missed
+                let notified = self.idle.notified();
+                if self.in_flight() == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+
+        match tokio::time::timeout(deadline, wait_idle).await {
+            Ok(()) => true,
+            Err(_) => {
+                warn!(in_flight = self.in_flight(), "Drain deadline exceeded");
+                false
+            }
+        }
+    }
+
+    fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+}
+
+/// Decrements the in-flight counter when a request finishes (or is cancelled)
+struct InFlightGuard<'a>(&'a DrainState);
+
+impl<'a> InFlightGuard<'a> {
+    fn new(state: &'a DrainState) -> Self {
+        state.in_flight.fetch_add(1, Ordering::AcqRel);
+        Self(state)
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+/// Request handler wrapper that tracks in-flight requests for graceful shutdown
+///
+/// hickory's own shutdown aborts requests that are still being resolved, so
+/// draining happens here first: once [`DrainState::drain`] is called, new
+/// requests are answered with REFUSED, so clients move on to another server
+/// right away, while the ones already accepted run to completion.
+pub struct DrainingHandler<H> {
+    inner: H,
+    state: Arc<DrainState>,
+}
+
+impl<H> DrainingHandler<H> {
+    pub fn new(inner: H, state: Arc<DrainState>) -> Self {
+        Self { inner, state }
+    }
+}
+
+#[async_trait]
+impl<H: RequestHandler> RequestHandler for DrainingHandler<H> {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handle: R,
+    ) -> ResponseInfo {
+        if self.state.is_draining() {
+            debug!(src = %request.src(), "Shutting down, refusing request");
+            let response = MessageResponseBuilder::from_message_request(request)
+                .error_msg(request.header(), ResponseCode::Refused);
+            return match response_handle.send_response(response).await {
+                Ok(info) => info,
+                Err(e) => {
+                    debug!(error = %e, "Failed to send refusal");
+                    let mut header = Header::response_from_request(request.header());
+                    header.set_response_code(ResponseCode::ServFail);
+                    header.into()
+                }
+            };
+        }
+
+        let _guard = InFlightGuard::new(&self.state);
+        self.inner.handle_request(request, response_handle).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_proto::op::{Message, MessageType, Query};
+    use hickory_proto::rr::{Name, Record, RecordType};
+    use hickory_proto::serialize::binary::{BinDecodable, BinEncoder};
+    use hickory_proto::xfer::Protocol;
+    use hickory_server::authority::{MessageRequest, MessageResponse};
+    use std::io;
+    use std::str::FromStr;
+    use std::sync::Mutex;
+
+    /// Keeps the response the handler sends
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Option<Message>>>);
+
+    #[async_trait]
+    impl ResponseHandler for Captured {
+        async fn send_response<'a>(
+            &mut self,
+            response: MessageResponse<
+                '_,
+                'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+                impl Iterator<Item = &'a Record> + Send + 'a,
+            >,
+        ) -> io::Result<ResponseInfo> {
+            let mut bytes = Vec::new();
+            let info = response.destructive_emit(&mut BinEncoder::new(&mut bytes))?;
+            *self.0.lock().unwrap() = Some(Message::from_vec(&bytes)?);
+            Ok(info)
+        }
+    }
+
+    /// Handler that must not see requests once draining starts
+    struct Unreachable;
+
+    #[async_trait]
+    impl RequestHandler for Unreachable {
+        async fn handle_request<R: ResponseHandler>(
+            &self,
+            _request: &Request,
+            _response_handle: R,
+        ) -> ResponseInfo {
+            panic!("request passed on while draining");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_draining_refuses_new_requests() {
+        let state = Arc::new(DrainState::default());
+        assert!(state.drain(Duration::from_millis(10)).await);
+        let handler = DrainingHandler::new(Unreachable, state);
+
+        let mut message = Message::new();
+        message.add_query(Query::query(Name::from_str("alice.near.").unwrap(), RecordType::A));
+        let bytes = message.to_vec().unwrap();
+        let request = Request::new(
+            MessageRequest::from_bytes(&bytes).unwrap(),
+            "127.0.0.1:5353".parse().unwrap(),
+            Protocol::Udp,
+        );
+
+        let captured = Captured::default();
+        handler.handle_request(&request, captured.clone()).await;
+        let response = captured.0.lock().unwrap().take().expect("no response sent");
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert_eq!(response.id(), message.id());
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_in_flight() {
+        let state = Arc::new(DrainState::default());
+        let guard_state = state.clone();
+
+        let request = tokio::spawn(async move {
+            let _guard = InFlightGuard::new(&guard_state);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        });
+        tokio::task::yield_now().await;
+
+        assert!(state.drain(Duration::from_secs(5)).await);
+        assert_eq!(state.in_flight(), 0);
+        assert!(state.is_draining());
+        request.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drain_deadline() {
+        let state = DrainState::default();
+        let _stuck = InFlightGuard::new(&state);
+
+        assert!(!state.drain(Duration::from_millis(10)).await);
+        assert_eq!(state.in_flight(), 1);
+    }
+}
@@ -0,0 +1,254 @@
+use async_trait::async_trait;
+use hickory_proto::rr::rdata::SOA;
+use hickory_proto::rr::{Name, RData, Record};
+use hickory_proto::ProtoError;
+use hickory_server::server::{Request, RequestHandler, ResponseHandler, ResponseInfo};
+use serde::Deserialize;
+use std::cell::RefCell;
+
+/// Defaults for the SOA records synthesized for NEAR zones
+///
+/// Every NEAR zone gets an SOA whose serial is the contract's zone serial.
+/// The other fields come from the zone's own `@` SOA record when it has one,
+/// and from here otherwise. `{zone}` in `mname` and `rname` is replaced with
+/// the zone name.
+///
+/// ```toml
+/// [soa]
+/// mname = "ns1.example.com."
+/// rname = "hostmaster.example.com."
+/// minimum = 60
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoaConfig {
+    /// Primary nameserver
+    pub mname: String,
+    /// Mailbox of the person responsible for the zone, with the `@` written as a dot
+    pub rname: String,
+    /// Seconds between secondary refreshes
+    pub refresh: i32,
+    /// Seconds before a failed refresh is retried
+    pub retry: i32,
+    /// Seconds after which a secondary that can't refresh stops answering
+    pub expire: i32,
+    /// Negative caching TTL in seconds; also the TTL of the SOA record itself
+    pub minimum: u32,
+}
+
+impl Default for SoaConfig {
+    fn default() -> Self {
+        Self {
+            mname: "ns.{zone}".to_string(),
+            rname: "hostmaster.{zone}".to_string(),
+            refresh: 3600,
+            retry: 600,
+            expire: 604_800,
+            minimum: 300,
+        }
+    }
+}
+
+impl SoaConfig {
+    /// Check that `mname` and `rname` expand to valid names
+    pub fn validate(&self) -> Result<(), ProtoError> {
+        let zone = Name::from_ascii("example.near.")?;
+        Self::expand(&self.mname, &zone)?;
+        Self::expand(&self.rname, &zone)?;
+        Ok(())
+    }
+
+    fn expand(template: &str, zone: &Name) -> Result<Name, ProtoError> {
+        let zone = zone.to_string();
+        let mut name = Name::from_ascii(template.replace("{zone}", &zone))?;
+        name.set_fqdn(true);
+        Ok(name)
+    }
+
+    /// Build the SOA record for `zone` at `serial`
+    ///
+    /// Fields other than the serial are taken from `stored` (the zone's own
+    /// SOA record, if any) so hand-written values are kept, but its serial is
+    /// always replaced. Serials are 32 bits in DNS; the contract's 64-bit
+    /// serial is truncated, which serial number arithmetic (RFC 1982) treats
+    /// as a wrap-around.
+    pub fn synthesize(
+        &self,
+        zone: &Name,
+        serial: u64,
+        stored: Option<&SOA>,
+    ) -> Result<Record, ProtoError> {
+        let serial = serial as u32;
+        let soa = match stored {
+            Some(stored) => SOA::new(
+                stored.mname().clone(),
+                stored.rname().clone(),
+                serial,
+                stored.refresh(),
+                stored.retry(),
+                stored.expire(),
+                stored.minimum(),
+            ),
+            None => SOA::new(
+                Self::expand(&self.mname, zone)?,
+                Self::expand(&self.rname, zone)?,
+                serial,
+                self.refresh,
+                self.retry,
+                self.expire,
+                self.minimum,
+            ),
+        };
+        let ttl = soa.minimum();
+        Ok(Record::from_rdata(zone.clone(), ttl, RData::SOA(soa)))
+    }
+}
+
+tokio::task_local! {
+    /// SOA of the NEAR zone a negative answer came from, for the authority section
+    static NEGATIVE_SOA: RefCell<Option<Record>>;
+}
+
+/// Remember the zone SOA to put in the authority section of the current
+/// request's negative answer
+///
+/// Does nothing outside a [`ZoneSoaHandler`].
+pub fn set_negative_soa(record: Record) {
+    let _ = NEGATIVE_SOA.try_with(|soa| *soa.borrow_mut() = Some(record));
+}
+
+/// Take the SOA recorded with [`set_negative_soa`] for the current request
+pub fn take_negative_soa() -> Option<Record> {
+    NEGATIVE_SOA
+        .try_with(|soa| soa.borrow_mut().take())
+        .ok()
+        .flatten()
+}
+
+/// Request handler wrapper that gives each request room for its zone's SOA
+///
+/// hickory fills the authority section of NXDOMAIN answers by asking the
+/// authority for the SOA of its origin, without saying which query it is for.
+/// Ours is the root, so the authority records the NEAR zone's SOA while
+/// answering the query and hands it back when the SOA is asked for; this
+/// handler scopes that per request.
+pub struct ZoneSoaHandler<H> {
+    inner: H,
+}
+
+impl<H> ZoneSoaHandler<H> {
+    pub fn new(inner: H) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl<H: RequestHandler> RequestHandler for ZoneSoaHandler<H> {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        response_handle: R,
+    ) -> ResponseInfo {
+        NEGATIVE_SOA
+            .scope(
+                RefCell::new(None),
+                self.inner.handle_request(request, response_handle),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn soa_of(record: &Record) -> &SOA {
+        match record.data() {
+            RData::SOA(soa) => soa,
+            other => panic!("expected SOA, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_synthesize_from_defaults() {
+        let zone = Name::from_str("alice.near.").unwrap();
+        let record = SoaConfig::default().synthesize(&zone, 42, None).unwrap();
+
+        assert_eq!(record.name(), &zone);
+        assert_eq!(record.ttl(), 300);
+        let soa = soa_of(&record);
+        assert_eq!(soa.serial(), 42);
+        assert_eq!(soa.mname(), &Name::from_str("ns.alice.near.").unwrap());
+        assert_eq!(soa.rname(), &Name::from_str("hostmaster.alice.near.").unwrap());
+
+        // Serials past 32 bits wrap around
+        let record = SoaConfig::default()
+            .synthesize(&zone, u32::MAX as u64 + 2, None)
+            .unwrap();
+        assert_eq!(soa_of(&record).serial(), 1);
+    }
+
+    #[test]
+    fn test_synthesize_keeps_stored_fields() {
+        let zone = Name::from_str("alice.near.").unwrap();
+        let stored = SOA::new(
+            Name::from_str("ns1.example.com.").unwrap(),
+            Name::from_str("admin.example.com.").unwrap(),
+            2024010101,
+            7200,
+            900,
+            1_209_600,
+            60,
+        );
+        let record = SoaConfig::default()
+            .synthesize(&zone, 7, Some(&stored))
+            .unwrap();
+
+        let soa = soa_of(&record);
+        assert_eq!(soa.serial(), 7);
+        assert_eq!(soa.mname(), stored.mname());
+        assert_eq!(soa.refresh(), 7200);
+        assert_eq!(record.ttl(), 60);
+    }
+
+    #[test]
+    fn test_config() {
+        let config: SoaConfig = toml::from_str(
+            r#"
+            mname = "ns1.example.com"
+            minimum = 60
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.minimum, 60);
+        assert_eq!(config.refresh, 3600);
+        assert!(config.validate().is_ok());
+
+        let config = SoaConfig {
+            rname: "bad..{zone}".to_string(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(toml::from_str::<SoaConfig>("serial = 1").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_negative_soa_is_per_request() {
+        let record = SoaConfig::default()
+            .synthesize(&Name::from_str("alice.near.").unwrap(), 1, None)
+            .unwrap();
+
+        // Outside a request there is nowhere to keep it
+        set_negative_soa(record.clone());
+        assert!(take_negative_soa().is_none());
+
+        let taken = NEGATIVE_SOA
+            .scope(RefCell::new(None), async {
+                set_negative_soa(record.clone());
+                take_negative_soa()
+            })
+            .await;
+        assert_eq!(taken, Some(record));
+    }
+}